@@ -0,0 +1,864 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use async_trait::async_trait;
+
+use crate::*;
+
+/* TMDB refuses to serve discover pages past this one, regardless of total_pages */
+const MAX_DISCOVER_PAGE: u32 = 500;
+
+/* Results per discover page, matching TMDB */
+const DISCOVER_PAGE_SIZE: usize = 20;
+
+/* Walks discover pages starting at start_page until count unique results are collected or fetch runs out of pages */
+/* Results in exclude_ids are skipped, so later pages backfill them */
+/* The returned page is the last page fetched, so callers can pick up from page + 1 */
+async fn collect_discover_pages<T, F, Fut>(
+    start_page: u32,
+    count: usize,
+    exclude_ids: HashSet<i64>,
+    id_of: fn(&T) -> i64,
+    mut fetch: F,
+) -> Result<PagedResponse<T>, TmdbError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<PagedResponse<T>, TmdbError>>,
+{
+    let mut seen_ids = exclude_ids;
+    let mut results: Vec<T> = vec![];
+    let mut page = start_page.max(1);
+
+    loop {
+        let response = fetch(page).await?;
+
+        for result in response.results {
+            if results.len() < count && seen_ids.insert(id_of(&result)) {
+                results.push(result);
+            }
+        }
+
+        let last_page = response.total_pages.min(MAX_DISCOVER_PAGE);
+
+        if results.len() >= count || page >= last_page {
+            return Ok(PagedResponse {
+                page,
+                results,
+                total_pages: response.total_pages,
+                total_results: response.total_results,
+            });
+        }
+
+        page += 1;
+    }
+}
+
+/* Source of movie metadata - Tmdb is the real one, anything else (fixtures, caching decorators, */
+/* other metadata services) just needs to answer the same questions */
+/* language is a tag like en-US or fr - catalogs that can't localize are free to ignore it */
+#[async_trait]
+pub trait MovieCatalog: Send + Sync {
+    /* Searches for movie by title - helpful for retrieving movie IDs */
+    /* year only matches a movie's primary release - page starts at 1 */
+    async fn search_by_title(
+        &self,
+        movie_title: &str,
+        year: Option<i32>,
+        page: u32,
+        language: &str,
+    ) -> Result<SearchByTitleResponse, TmdbError>;
+
+    async fn get_keywords_for_id(&self, movie_id: &i64) -> Result<KeywordResponse, TmdbError>;
+
+    /* Searches keywords by name - page starts at 1 */
+    async fn search_keywords(
+        &self,
+        query: &str,
+        page: u32,
+    ) -> Result<SearchKeywordsResponse, TmdbError>;
+
+    /* Runtime, genres, tagline, votes and the rest of what a detail page needs */
+    async fn get_movie_details(
+        &self,
+        movie_id: &i64,
+        language: &str,
+    ) -> Result<MovieDetails, TmdbError>;
+
+    /* TMDB's picks for people who liked this movie - page starts at 1 */
+    async fn get_recommendations_for_movie(
+        &self,
+        movie_id: &i64,
+        page: u32,
+        language: &str,
+    ) -> Result<GetRecommendationsResponse, TmdbError>;
+
+    /* Movies sharing this movie's genres and keywords - page starts at 1 */
+    async fn get_similar_movies(
+        &self,
+        movie_id: &i64,
+        page: u32,
+        language: &str,
+    ) -> Result<GetRecommendationsResponse, TmdbError>;
+
+    /* Trailers, teasers, clips etc - see VideosResponse::best_trailer */
+    async fn get_videos_for_id(
+        &self,
+        movie_id: &i64,
+        language: &str,
+    ) -> Result<VideosResponse, TmdbError>;
+
+    async fn get_credits_for_id(
+        &self,
+        movie_id: &i64,
+        language: &str,
+    ) -> Result<CreditsResponse, TmdbError>;
+
+    /* Searches for actors, directors etc by name - helpful for retrieving person IDs */
+    async fn search_people(
+        &self,
+        name: &str,
+        language: &str,
+    ) -> Result<SearchPeopleResponse, TmdbError>;
+
+    /* Watch providers for a movie, broken down by region and monetization type */
+    async fn get_watch_providers_by_id(
+        &self,
+        movie_id: &str,
+    ) -> Result<GetWatchProvidersResponse, TmdbError>;
+
+    async fn get_genre_list(&self, language: &str) -> Result<GetGenresResponse, TmdbError>;
+
+    /* Image host and sizes used to turn path fragments into absolute URLs */
+    async fn get_image_configuration(&self) -> Result<ImageConfiguration, TmdbError>;
+
+    /* Every watch provider available in a region */
+    async fn get_providers_list(
+        &self,
+        region: &str,
+        language: &str,
+    ) -> Result<GetProvidersResponse, TmdbError>;
+
+    /* A single page of discover results - criteria that haven't been set aren't filtered on */
+    async fn get_recommendations(
+        &self,
+        criteria: &RecommendationCriteria,
+        page: u32,
+    ) -> Result<GetRecommendationsResponse, TmdbError>;
+
+    /* Walks movie discover pages until count unique movies are collected - see collect_discover_pages */
+    /* Movies the session has already seen don't count */
+    async fn get_recommendation_pages(
+        &self,
+        criteria: &RecommendationCriteria,
+        start_page: u32,
+        count: usize,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        collect_discover_pages(
+            start_page,
+            count,
+            criteria.seen_ids(MediaType::Movie),
+            |movie: &Movie| movie.id,
+            |page| self.get_recommendations(criteria, page),
+        )
+        .await
+    }
+
+    /* A single page of TV discover results - cast and crew aren't filtered on */
+    async fn get_tv_recommendations(
+        &self,
+        criteria: &RecommendationCriteria,
+        page: u32,
+    ) -> Result<GetTvRecommendationsResponse, TmdbError>;
+
+    async fn get_tv_recommendation_pages(
+        &self,
+        criteria: &RecommendationCriteria,
+        start_page: u32,
+        count: usize,
+    ) -> Result<GetTvRecommendationsResponse, TmdbError> {
+        collect_discover_pages(
+            start_page,
+            count,
+            criteria.seen_ids(MediaType::Tv),
+            |show: &TvShow| show.id,
+            |page| self.get_tv_recommendations(criteria, page),
+        )
+        .await
+    }
+
+    /* Episode runtimes, seasons, air dates and the rest of what a series page needs */
+    async fn get_tv_details(&self, show_id: &i64, language: &str) -> Result<TvDetails, TmdbError>;
+
+    async fn get_tv_keywords_for_id(&self, show_id: &i64) -> Result<KeywordResponse, TmdbError>;
+
+    async fn get_tv_watch_providers_by_id(
+        &self,
+        show_id: &str,
+    ) -> Result<GetWatchProvidersResponse, TmdbError>;
+
+    async fn get_tv_genre_list(&self, language: &str) -> Result<GetGenresResponse, TmdbError>;
+
+    async fn get_tv_providers_list(
+        &self,
+        region: &str,
+        language: &str,
+    ) -> Result<GetProvidersResponse, TmdbError>;
+
+    /* Hit/miss counters for catalogs that cache lookups */
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+}
+
+/* Catalog backed by fixtures held in memory - meant for tests and local development */
+/* Discover only filters on decade, watch providers, and people and vote bounds (movies only), results are returned in the order they were added */
+/* Nothing is localized, so language is ignored everywhere */
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryCatalog {
+    movies: Vec<Movie>,
+    keywords: HashMap<i64, Vec<Keyword>>,
+    details: HashMap<i64, MovieDetails>,
+    credits: HashMap<i64, CreditsResponse>,
+    videos: HashMap<i64, Vec<Video>>,
+    movie_recommendations: HashMap<i64, Vec<i64>>,
+    similar: HashMap<i64, Vec<i64>>,
+    people: Vec<Person>,
+    watch_providers: HashMap<i64, WatchProviderRegions>,
+    genres: Vec<Genre>,
+    providers: HashMap<String, Vec<WatchProvider>>,
+    shows: Vec<TvShow>,
+    tv_details: HashMap<i64, TvDetails>,
+    tv_keywords: HashMap<i64, Vec<Keyword>>,
+    tv_watch_providers: HashMap<i64, WatchProviderRegions>,
+    tv_genres: Vec<Genre>,
+    tv_providers: HashMap<String, Vec<WatchProvider>>,
+}
+
+impl InMemoryCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_movie(mut self, movie: Movie) -> Self {
+        self.movies.push(movie);
+        self
+    }
+
+    pub fn with_keywords(mut self, movie_id: i64, keywords: Vec<Keyword>) -> Self {
+        self.keywords.insert(movie_id, keywords);
+        self
+    }
+
+    pub fn with_details(mut self, details: MovieDetails) -> Self {
+        self.details.insert(details.id, details);
+        self
+    }
+
+    pub fn with_videos(mut self, movie_id: i64, videos: Vec<Video>) -> Self {
+        self.videos.insert(movie_id, videos);
+        self
+    }
+
+    pub fn with_credits(mut self, credits: CreditsResponse) -> Self {
+        self.credits.insert(credits.id, credits);
+        self
+    }
+
+    /* Related movies are given by ID and looked up among the movies added with with_movie */
+    pub fn with_movie_recommendations(mut self, movie_id: i64, related_ids: Vec<i64>) -> Self {
+        self.movie_recommendations.insert(movie_id, related_ids);
+        self
+    }
+
+    pub fn with_similar(mut self, movie_id: i64, similar_ids: Vec<i64>) -> Self {
+        self.similar.insert(movie_id, similar_ids);
+        self
+    }
+
+    pub fn with_person(mut self, person: Person) -> Self {
+        self.people.push(person);
+        self
+    }
+
+    pub fn with_watch_providers(mut self, movie_id: i64, regions: WatchProviderRegions) -> Self {
+        self.watch_providers.insert(movie_id, regions);
+        self
+    }
+
+    pub fn with_genres(mut self, genres: Vec<Genre>) -> Self {
+        self.genres = genres;
+        self
+    }
+
+    pub fn with_providers(mut self, region: &str, providers: Vec<WatchProvider>) -> Self {
+        self.providers.insert(region.to_string(), providers);
+        self
+    }
+
+    pub fn with_show(mut self, show: TvShow) -> Self {
+        self.shows.push(show);
+        self
+    }
+
+    pub fn with_tv_details(mut self, details: TvDetails) -> Self {
+        self.tv_details.insert(details.id, details);
+        self
+    }
+
+    pub fn with_tv_keywords(mut self, show_id: i64, keywords: Vec<Keyword>) -> Self {
+        self.tv_keywords.insert(show_id, keywords);
+        self
+    }
+
+    pub fn with_tv_watch_providers(mut self, show_id: i64, regions: WatchProviderRegions) -> Self {
+        self.tv_watch_providers.insert(show_id, regions);
+        self
+    }
+
+    pub fn with_tv_genres(mut self, genres: Vec<Genre>) -> Self {
+        self.tv_genres = genres;
+        self
+    }
+
+    pub fn with_tv_providers(mut self, region: &str, providers: Vec<WatchProvider>) -> Self {
+        self.tv_providers.insert(region.to_string(), providers);
+        self
+    }
+
+    /* Movies without an entry for movie_id get an empty page, like TMDB does for obscure titles */
+    fn related_page(
+        &self,
+        related: &HashMap<i64, Vec<i64>>,
+        movie_id: &i64,
+        page: u32,
+    ) -> GetRecommendationsResponse {
+        let matching: Vec<&Movie> = related
+            .get(movie_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.movies.iter().find(|m| m.id == *id))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self::page_of(matching, page)
+    }
+
+    fn not_found(what: String) -> TmdbError {
+        TmdbError::NotFound {
+            status_message: Some(what),
+        }
+    }
+
+    fn matches_decade(date: &str, decade: &Option<Decade>) -> bool {
+        match decade {
+            Some(decade) => decade.contains_date(date),
+            None => true,
+        }
+    }
+
+    /* Movies without credits never match once cast or crew has been picked */
+    fn matches_people(&self, movie: &Movie, criteria: &RecommendationCriteria) -> bool {
+        let credits = self.credits.get(&movie.id);
+
+        let cast_matches = match &criteria.with_cast {
+            Some(cast) => credits
+                .map(|c| c.cast.iter().any(|m| cast.iter().any(|p| p.id == m.id)))
+                .unwrap_or(false),
+            None => true,
+        };
+
+        let crew_matches = match &criteria.with_crew {
+            Some(crew) => credits
+                .map(|c| c.crew.iter().any(|m| crew.iter().any(|p| p.id == m.id)))
+                .unwrap_or(false),
+            None => true,
+        };
+
+        cast_matches && crew_matches
+    }
+
+    fn matches_providers(
+        watch_providers_by_id: &HashMap<i64, WatchProviderRegions>,
+        id: i64,
+        criteria: &RecommendationCriteria,
+    ) -> bool {
+        let watch_providers = match &criteria.watch_providers {
+            Some(watch_providers) => watch_providers,
+            None => return true,
+        };
+
+        let available = match watch_providers_by_id
+            .get(&id)
+            .and_then(|regions| regions.get(criteria.region()))
+        {
+            Some(available) => available,
+            None => return false,
+        };
+
+        available
+            .only(&criteria.monetization_types())
+            .offers_any(watch_providers)
+    }
+
+    fn page_of<T: Clone>(matching: Vec<&T>, page: u32) -> PagedResponse<T> {
+        let total_results = matching.len() as u32;
+        let total_pages = (matching.len().div_ceil(DISCOVER_PAGE_SIZE) as u32).max(1);
+        let page = page.max(1);
+
+        PagedResponse {
+            page,
+            results: matching
+                .into_iter()
+                .skip((page as usize - 1) * DISCOVER_PAGE_SIZE)
+                .take(DISCOVER_PAGE_SIZE)
+                .cloned()
+                .collect(),
+            total_pages,
+            total_results,
+        }
+    }
+}
+
+#[async_trait]
+impl MovieCatalog for InMemoryCatalog {
+    async fn search_by_title(
+        &self,
+        movie_title: &str,
+        year: Option<i32>,
+        page: u32,
+        _language: &str,
+    ) -> Result<SearchByTitleResponse, TmdbError> {
+        let query = movie_title.to_lowercase();
+        let year = year.map(|year| year.to_string());
+
+        let matching: Vec<&Movie> = self
+            .movies
+            .iter()
+            .filter(|m| m.title.to_lowercase().contains(&query))
+            .filter(|m| match &year {
+                Some(year) => m.release_date.starts_with(year.as_str()),
+                None => true,
+            })
+            .collect();
+
+        Ok(Self::page_of(matching, page))
+    }
+
+    async fn get_keywords_for_id(&self, movie_id: &i64) -> Result<KeywordResponse, TmdbError> {
+        match self.keywords.get(movie_id) {
+            Some(keywords) => Ok(KeywordResponse {
+                id: *movie_id,
+                keywords: keywords.clone(),
+            }),
+            None => Err(Self::not_found(format!("No keywords for {}", movie_id))),
+        }
+    }
+
+    /* Searches every keyword attached to a movie or show */
+    async fn search_keywords(
+        &self,
+        query: &str,
+        page: u32,
+    ) -> Result<SearchKeywordsResponse, TmdbError> {
+        let query = query.to_lowercase();
+        let mut seen_ids: HashSet<i64> = HashSet::new();
+
+        let mut matching: Vec<&Keyword> = self
+            .keywords
+            .values()
+            .chain(self.tv_keywords.values())
+            .flatten()
+            .filter(|k| k.name.to_lowercase().contains(&query))
+            .filter(|k| seen_ids.insert(k.id))
+            .collect();
+
+        // Keywords are held in hash maps, so order them to keep pages stable
+        matching.sort_by_key(|k| k.id);
+
+        Ok(Self::page_of(matching, page))
+    }
+
+    async fn get_movie_details(
+        &self,
+        movie_id: &i64,
+        _language: &str,
+    ) -> Result<MovieDetails, TmdbError> {
+        match self.details.get(movie_id) {
+            Some(details) => Ok(details.clone()),
+            None => Err(Self::not_found(format!("No details for {}", movie_id))),
+        }
+    }
+
+    async fn get_recommendations_for_movie(
+        &self,
+        movie_id: &i64,
+        page: u32,
+        _language: &str,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        Ok(self.related_page(&self.movie_recommendations, movie_id, page))
+    }
+
+    async fn get_similar_movies(
+        &self,
+        movie_id: &i64,
+        page: u32,
+        _language: &str,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        Ok(self.related_page(&self.similar, movie_id, page))
+    }
+
+    /* Plenty of movies have no videos, so that's an empty list rather than an error */
+    async fn get_videos_for_id(
+        &self,
+        movie_id: &i64,
+        _language: &str,
+    ) -> Result<VideosResponse, TmdbError> {
+        Ok(VideosResponse {
+            id: *movie_id,
+            results: self.videos.get(movie_id).cloned().unwrap_or_default(),
+        })
+    }
+
+    async fn get_credits_for_id(
+        &self,
+        movie_id: &i64,
+        _language: &str,
+    ) -> Result<CreditsResponse, TmdbError> {
+        match self.credits.get(movie_id) {
+            Some(credits) => Ok(credits.clone()),
+            None => Err(Self::not_found(format!("No credits for {}", movie_id))),
+        }
+    }
+
+    async fn search_people(
+        &self,
+        name: &str,
+        _language: &str,
+    ) -> Result<SearchPeopleResponse, TmdbError> {
+        let query = name.to_lowercase();
+
+        Ok(SearchPeopleResponse {
+            results: self
+                .people
+                .iter()
+                .filter(|p| p.name.to_lowercase().contains(&query))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    async fn get_watch_providers_by_id(
+        &self,
+        movie_id: &str,
+    ) -> Result<GetWatchProvidersResponse, TmdbError> {
+        match movie_id
+            .parse::<i64>()
+            .ok()
+            .and_then(|id| self.watch_providers.get(&id))
+        {
+            Some(regions) => Ok(GetWatchProvidersResponse {
+                results: regions.clone(),
+            }),
+            None => Err(Self::not_found(format!(
+                "No watch providers for {}",
+                movie_id
+            ))),
+        }
+    }
+
+    async fn get_genre_list(&self, _language: &str) -> Result<GetGenresResponse, TmdbError> {
+        Ok(GetGenresResponse {
+            genres: self.genres.clone(),
+        })
+    }
+
+    /* Images always point at TMDB's usual host */
+    async fn get_image_configuration(&self) -> Result<ImageConfiguration, TmdbError> {
+        Ok(ImageConfiguration::default())
+    }
+
+    async fn get_providers_list(
+        &self,
+        region: &str,
+        _language: &str,
+    ) -> Result<GetProvidersResponse, TmdbError> {
+        Ok(GetProvidersResponse {
+            results: self.providers.get(region).cloned().unwrap_or_default(),
+        })
+    }
+
+    async fn get_recommendations(
+        &self,
+        criteria: &RecommendationCriteria,
+        page: u32,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        let vote_bounds = criteria.vote_bounds();
+        let matching: Vec<&Movie> = self
+            .movies
+            .iter()
+            .filter(|m| Self::matches_decade(&m.release_date, &criteria.decade))
+            .filter(|m| Self::matches_providers(&self.watch_providers, m.id, criteria))
+            .filter(|m| self.matches_people(m, criteria))
+            .filter(|m| vote_bounds.contains(m.vote_average, m.vote_count))
+            .collect();
+
+        Ok(Self::page_of(matching, page))
+    }
+
+    async fn get_tv_recommendations(
+        &self,
+        criteria: &RecommendationCriteria,
+        page: u32,
+    ) -> Result<GetTvRecommendationsResponse, TmdbError> {
+        let matching: Vec<&TvShow> = self
+            .shows
+            .iter()
+            .filter(|s| Self::matches_decade(&s.first_air_date, &criteria.decade))
+            .filter(|s| Self::matches_providers(&self.tv_watch_providers, s.id, criteria))
+            .collect();
+
+        Ok(Self::page_of(matching, page))
+    }
+
+    async fn get_tv_details(&self, show_id: &i64, _language: &str) -> Result<TvDetails, TmdbError> {
+        match self.tv_details.get(show_id) {
+            Some(details) => Ok(details.clone()),
+            None => Err(Self::not_found(format!("No details for show {}", show_id))),
+        }
+    }
+
+    async fn get_tv_keywords_for_id(&self, show_id: &i64) -> Result<KeywordResponse, TmdbError> {
+        match self.tv_keywords.get(show_id) {
+            Some(keywords) => Ok(KeywordResponse {
+                id: *show_id,
+                keywords: keywords.clone(),
+            }),
+            None => Err(Self::not_found(format!("No keywords for show {}", show_id))),
+        }
+    }
+
+    async fn get_tv_watch_providers_by_id(
+        &self,
+        show_id: &str,
+    ) -> Result<GetWatchProvidersResponse, TmdbError> {
+        match show_id
+            .parse::<i64>()
+            .ok()
+            .and_then(|id| self.tv_watch_providers.get(&id))
+        {
+            Some(regions) => Ok(GetWatchProvidersResponse {
+                results: regions.clone(),
+            }),
+            None => Err(Self::not_found(format!(
+                "No watch providers for show {}",
+                show_id
+            ))),
+        }
+    }
+
+    async fn get_tv_genre_list(&self, _language: &str) -> Result<GetGenresResponse, TmdbError> {
+        Ok(GetGenresResponse {
+            genres: self.tv_genres.clone(),
+        })
+    }
+
+    async fn get_tv_providers_list(
+        &self,
+        region: &str,
+        _language: &str,
+    ) -> Result<GetProvidersResponse, TmdbError> {
+        Ok(GetProvidersResponse {
+            results: self.tv_providers.get(region).cloned().unwrap_or_default(),
+        })
+    }
+}
+
+/* ======================================================================================================================== */
+/* ====================================================== UNIT TESTS ====================================================== */
+/* ======================================================================================================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie(id: i64, title: &str, release_date: &str) -> Movie {
+        Movie {
+            id,
+            overview: String::new(),
+            popularity: 0.0,
+            poster_path: None,
+            poster_url: None,
+            release_date: release_date.to_string(),
+            title: title.to_string(),
+            vote_average: 0.0,
+            vote_count: 0,
+            genre_ids: vec![],
+        }
+    }
+
+    fn netflix() -> WatchProvider {
+        WatchProvider {
+            logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
+            logo_url: None,
+            provider_id: 8,
+            provider_name: "Netflix".to_string(),
+        }
+    }
+
+    fn on_netflix(region: &str) -> WatchProviderRegions {
+        let mut regions = WatchProviderRegions::new();
+        regions.insert(
+            region.to_string(),
+            WatchProviderRegion {
+                flatrate: vec![netflix()],
+                ..WatchProviderRegion::default()
+            },
+        );
+        regions
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_lookups() {
+        let catalog = InMemoryCatalog::new()
+            .with_movie(movie(1, "Blade Runner", "1982-06-25"))
+            .with_movie(movie(2, "Blade Runner 2049", "2017-10-04"))
+            .with_movie(movie(3, "Arrival", "2016-11-10"))
+            .with_keywords(
+                2,
+                vec![Keyword {
+                    id: 4565,
+                    name: "dystopia".to_string(),
+                }],
+            )
+            .with_watch_providers(2, on_netflix("US"));
+
+        let search = catalog
+            .search_by_title("blade runner", None, 1, DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+        assert_eq!(search.results.len(), 2);
+        assert_eq!(search.total_results, 2);
+
+        let search = catalog
+            .search_by_title("blade runner", Some(2017), 1, DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+        assert_eq!(search.results[0].id, 2);
+        assert_eq!(search.total_results, 1);
+
+        let keywords = catalog.get_keywords_for_id(&2).await.unwrap();
+        assert_eq!(keywords.keywords[0].name, "dystopia");
+
+        let providers = catalog.get_watch_providers_by_id("2").await.unwrap();
+        assert_eq!(providers.flatrate_for_region("US"), vec![netflix()]);
+
+        assert!(matches!(
+            catalog.get_keywords_for_id(&3).await,
+            Err(TmdbError::NotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_discover() {
+        let mut catalog = InMemoryCatalog::new();
+
+        for id in 1..=45 {
+            catalog = catalog
+                .with_movie(movie(id, &format!("Movie {}", id), "2015-01-01"))
+                .with_watch_providers(id, on_netflix("US"));
+        }
+
+        // Right decade but not streaming anywhere, and streaming but from the wrong decade
+        catalog = catalog
+            .with_movie(movie(100, "Nowhere", "2015-01-01"))
+            .with_movie(movie(101, "Too Old", "1975-01-01"))
+            .with_watch_providers(101, on_netflix("US"));
+
+        let criteria = RecommendationCriteria {
+            watch_providers: Some(vec![netflix()]),
+            decade: Some(Decade::TwentyTens),
+            ..RecommendationCriteria::default()
+        };
+
+        let first_page = catalog.get_recommendations(&criteria, 1).await.unwrap();
+        assert_eq!(first_page.results.len(), 20);
+        assert_eq!(first_page.total_pages, 3);
+        assert_eq!(first_page.total_results, 45);
+
+        let pages = catalog
+            .get_recommendation_pages(&criteria, 1, 30)
+            .await
+            .unwrap();
+        assert_eq!(pages.page, 2);
+        assert_eq!(
+            pages.results.iter().map(|m| m.id).collect::<Vec<_>>(),
+            (1..=30).collect::<Vec<_>>()
+        );
+
+        // Movies the session has seen are skipped, and later pages make up for them
+        let mut seen_criteria = criteria.clone();
+        seen_criteria.mark_seen(MediaType::Movie, 1..=25);
+        let pages = catalog
+            .get_recommendation_pages(&seen_criteria, 1, 15)
+            .await
+            .unwrap();
+        assert_eq!(pages.page, 2);
+        assert_eq!(
+            pages.results.iter().map(|m| m.id).collect::<Vec<_>>(),
+            (26..=40).collect::<Vec<_>>()
+        );
+
+        // Only streaming in the US
+        let canada = RecommendationCriteria {
+            region: Some("CA".to_string()),
+            ..criteria
+        };
+        let response = catalog.get_recommendations(&canada, 1).await.unwrap();
+        assert!(response.results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_people() {
+        let villeneuve = Person {
+            id: 137427,
+            name: "Denis Villeneuve".to_string(),
+            known_for_department: Some("Directing".to_string()),
+            profile_path: None,
+        };
+
+        let catalog = InMemoryCatalog::new()
+            .with_movie(movie(1, "Arrival", "2016-11-10"))
+            .with_movie(movie(2, "Blade Runner", "1982-06-25"))
+            .with_person(villeneuve.clone())
+            .with_credits(CreditsResponse {
+                id: 1,
+                cast: vec![],
+                crew: vec![CrewMember {
+                    id: 137427,
+                    name: "Denis Villeneuve".to_string(),
+                    department: "Directing".to_string(),
+                    job: "Director".to_string(),
+                    profile_path: None,
+                }],
+            });
+
+        let people = catalog
+            .search_people("villeneuve", DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+        assert_eq!(people.results, vec![villeneuve.clone()]);
+
+        let criteria = RecommendationCriteria {
+            with_crew: Some(vec![villeneuve]),
+            ..RecommendationCriteria::default()
+        };
+
+        // Blade Runner has no credits, so it can't match
+        let response = catalog.get_recommendations(&criteria, 1).await.unwrap();
+        assert_eq!(
+            response.results.iter().map(|m| m.id).collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+}
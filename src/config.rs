@@ -0,0 +1,509 @@
+use std::{fmt, fs, time::Duration};
+
+use serde::Deserialize;
+
+use crate::TmdbClientConfig;
+
+/* Where the config file is read from unless APP_CONFIG points somewhere else - it's fine for it not to exist */
+pub const DEFAULT_CONFIG_PATH: &str = "config/app.toml";
+
+pub const DEFAULT_REDIS_URL: &str = "redis://localhost:6379";
+
+/* Errors found while loading config - reported once at startup instead of panicking somewhere later */
+#[derive(Debug)]
+pub enum ConfigError {
+    /* The config file exists but couldn't be read */
+    Read {
+        path: String,
+        error: std::io::Error,
+    },
+    /* The config file isn't valid TOML, or has a value of the wrong type */
+    Parse {
+        path: String,
+        error: toml::de::Error,
+    },
+    /* An environment override couldn't be parsed */
+    Env {
+        name: &'static str,
+        value: String,
+    },
+    /* Everything parsed, but a setting doesn't make sense */
+    Invalid {
+        setting: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => {
+                write!(f, "Unable to read config file {}: {}", path, error)
+            }
+            ConfigError::Parse { path, error } => {
+                write!(f, "Unable to parse config file {}: {}", path, error)
+            }
+            ConfigError::Env { name, value } => {
+                write!(
+                    f,
+                    "Environment variable {} has an invalid value: {}",
+                    name, value
+                )
+            }
+            ConfigError::Invalid { setting, reason } => {
+                write!(f, "Invalid {}: {}", setting, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { error, .. } => Some(error),
+            ConfigError::Parse { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    /* Defaults to one worker per CPU core when not set */
+    pub workers: Option<usize>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: String::from("0.0.0.0"),
+            port: 8585,
+            workers: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TmdbConfig {
+    pub base_url: String,
+    /* Takes priority over api_key_file - usually set through TMDB_API_KEY rather than in the file */
+    pub api_key: Option<String>,
+    pub api_key_file: String,
+    pub connect_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for TmdbConfig {
+    fn default() -> Self {
+        Self {
+            base_url: String::from("https://api.themoviedb.org/3"),
+            api_key: None,
+            api_key_file: String::from("config/api.key"),
+            connect_timeout_secs: 5,
+            request_timeout_secs: 15,
+        }
+    }
+}
+
+impl TmdbConfig {
+    /* HTTP client settings - anything not configurable here keeps its default */
+    pub fn client_config(&self) -> TmdbClientConfig {
+        TmdbClientConfig {
+            connect_timeout: Duration::from_secs(self.connect_timeout_secs),
+            request_timeout: Duration::from_secs(self.request_timeout_secs),
+            ..TmdbClientConfig::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    pub url: String,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            url: String::from(DEFAULT_REDIS_URL),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /* "*" allows any origin, which is what the frontend relies on during development */
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![String::from("*")],
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /* Largest JSON body accepted by POST handlers */
+    pub json_payload_bytes: usize,
+    /* Number of TMDB responses held in process - keywords and providers are a few KB each */
+    pub tmdb_cache_capacity: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            json_payload_bytes: 64 * 1024,
+            tmdb_cache_capacity: 5000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedbackConfig {
+    /* Older keyword votes are multiplied by this each time new feedback comes in - 1.0 keeps them forever */
+    pub keyword_decay: f64,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self { keyword_decay: 1.0 }
+    }
+}
+
+/* Everything the server needs to start - read from a TOML file, then overridden by environment variables */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub tmdb: TmdbConfig,
+    pub redis: RedisConfig,
+    pub cors: CorsConfig,
+    pub limits: LimitsConfig,
+    pub feedback: FeedbackConfig,
+}
+
+impl AppConfig {
+    /* Reads the config file and process environment, resolves the API key and validates the result */
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = std::env::var("APP_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+
+        let file = match fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(ConfigError::Read { path, error }),
+        };
+
+        let mut config =
+            Self::from_sources(&path, file.as_deref(), |name| std::env::var(name).ok())?;
+        config.resolve_api_key()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /* Builds config from file contents and an environment lookup - split out of load so it can be tested */
+    pub fn from_sources(
+        path: &str,
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut config: AppConfig = match file {
+            Some(contents) => toml::from_str(contents).map_err(|error| ConfigError::Parse {
+                path: path.to_string(),
+                error,
+            })?,
+            None => AppConfig::default(),
+        };
+
+        if let Some(value) = env("BIND_ADDRESS") {
+            config.server.bind_address = value;
+        }
+        if let Some(value) = env("PORT") {
+            config.server.port = Self::parse_env("PORT", value)?;
+        }
+        if let Some(value) = env("WORKERS") {
+            config.server.workers = Some(Self::parse_env("WORKERS", value)?);
+        }
+        if let Some(value) = env("TMDB_BASE_URL") {
+            config.tmdb.base_url = value;
+        }
+        if let Some(value) = env("TMDB_API_KEY") {
+            config.tmdb.api_key = Some(value);
+        }
+        if let Some(value) = env("TMDB_API_KEY_FILE") {
+            config.tmdb.api_key_file = value;
+        }
+        // Kept under its original name so existing deployments don't need changing
+        if let Some(value) = env("REDIS_CONNECTION_STRING") {
+            config.redis.url = value;
+        }
+        if let Some(value) = env("CORS_ALLOWED_ORIGINS") {
+            config.cors.allowed_origins = value
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+        if let Some(value) = env("JSON_PAYLOAD_BYTES") {
+            config.limits.json_payload_bytes = Self::parse_env("JSON_PAYLOAD_BYTES", value)?;
+        }
+        if let Some(value) = env("TMDB_CACHE_CAPACITY") {
+            config.limits.tmdb_cache_capacity = Self::parse_env("TMDB_CACHE_CAPACITY", value)?;
+        }
+        if let Some(value) = env("KEYWORD_DECAY") {
+            config.feedback.keyword_decay = Self::parse_env("KEYWORD_DECAY", value)?;
+        }
+
+        Ok(config)
+    }
+
+    /* Reads the API key from api_key_file unless one was given directly */
+    pub fn resolve_api_key(&mut self) -> Result<(), ConfigError> {
+        if self.tmdb.api_key.is_some() {
+            return Ok(());
+        }
+
+        match fs::read_to_string(&self.tmdb.api_key_file) {
+            Ok(key) => {
+                self.tmdb.api_key = Some(key.trim().to_string());
+                Ok(())
+            }
+            Err(error) => Err(ConfigError::Invalid {
+                setting: "tmdb.api_key",
+                reason: format!(
+                    "not set, and {} couldn't be read ({}) - set TMDB_API_KEY or tmdb.api_key_file",
+                    self.tmdb.api_key_file, error
+                ),
+            }),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid =
+            |setting: &'static str, reason: String| Err(ConfigError::Invalid { setting, reason });
+
+        match &self.tmdb.api_key {
+            Some(key) if !key.trim().is_empty() => {}
+            _ => return invalid("tmdb.api_key", String::from("must not be empty")),
+        }
+
+        if !self.tmdb.base_url.starts_with("http://") && !self.tmdb.base_url.starts_with("https://")
+        {
+            return invalid(
+                "tmdb.base_url",
+                format!("{} is not an http(s) URL", self.tmdb.base_url),
+            );
+        }
+
+        if self.tmdb.connect_timeout_secs == 0 || self.tmdb.request_timeout_secs == 0 {
+            return invalid("tmdb timeouts", String::from("must be at least one second"));
+        }
+
+        if self.server.bind_address.trim().is_empty() {
+            return invalid("server.bind_address", String::from("must not be empty"));
+        }
+
+        if self.server.workers == Some(0) {
+            return invalid("server.workers", String::from("must be at least 1"));
+        }
+
+        if let Err(err) = redis::Client::open(self.redis.url.as_str()) {
+            return invalid("redis.url", format!("{} ({})", self.redis.url, err));
+        }
+
+        if self.cors.allowed_origins.is_empty() {
+            return invalid(
+                "cors.allowed_origins",
+                String::from("must list at least one origin, or \"*\" for any"),
+            );
+        }
+
+        if let Some(origin) = self.cors.allowed_origins.iter().find(|origin| {
+            origin.as_str() != "*"
+                && !origin.starts_with("http://")
+                && !origin.starts_with("https://")
+        }) {
+            return invalid(
+                "cors.allowed_origins",
+                format!("{} is not an http(s) origin", origin),
+            );
+        }
+
+        if self.limits.json_payload_bytes == 0 {
+            return invalid(
+                "limits.json_payload_bytes",
+                String::from("must be at least 1"),
+            );
+        }
+
+        if self.limits.tmdb_cache_capacity == 0 {
+            return invalid(
+                "limits.tmdb_cache_capacity",
+                String::from("must be at least 1"),
+            );
+        }
+
+        // Zero would forget everything but the latest round, above one would amplify old votes
+        if !(self.feedback.keyword_decay > 0.0 && self.feedback.keyword_decay <= 1.0) {
+            return invalid(
+                "feedback.keyword_decay",
+                format!(
+                    "{} is not between 0 (exclusive) and 1",
+                    self.feedback.keyword_decay
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn parse_env<T: std::str::FromStr>(
+        name: &'static str,
+        value: String,
+    ) -> Result<T, ConfigError> {
+        value
+            .trim()
+            .parse::<T>()
+            .map_err(|_| ConfigError::Env { name, value })
+    }
+}
+
+/* ======================================================================================================================== */
+/* ====================================================== UNIT TESTS ====================================================== */
+/* ======================================================================================================================== */
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn load(file: Option<&str>, env: &[(&str, &str)]) -> Result<AppConfig, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        let config = AppConfig::from_sources("app.toml", file, |name| env.get(name).cloned())?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn test_config_defaults_and_overrides() {
+        let file = r#"
+            [server]
+            port = 9000
+            workers = 4
+
+            [tmdb]
+            api_key = "from-file"
+
+            [cors]
+            allowed_origins = ["https://movies.example.com"]
+        "#;
+
+        let config = load(
+            Some(file),
+            &[
+                ("PORT", "9100"),
+                ("REDIS_CONNECTION_STRING", "redis://cache:6379"),
+            ],
+        )
+        .unwrap();
+
+        // Environment wins over the file, the file wins over defaults
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.server.workers, Some(4));
+        assert_eq!(config.server.bind_address, "0.0.0.0");
+        assert_eq!(config.tmdb.api_key.as_deref(), Some("from-file"));
+        assert_eq!(config.tmdb.base_url, "https://api.themoviedb.org/3");
+        assert_eq!(config.redis.url, "redis://cache:6379");
+        assert!(!config.cors.allows_any_origin());
+        assert_eq!(config.limits.tmdb_cache_capacity, 5000);
+        assert_eq!(config.feedback.keyword_decay, 1.0);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let with_key = [("TMDB_API_KEY", "supersecret")];
+
+        assert!(matches!(
+            load(None, &[("TMDB_API_KEY", "supersecret"), ("PORT", "eighty")]),
+            Err(ConfigError::Env { name: "PORT", .. })
+        ));
+
+        assert!(matches!(
+            load(Some("[server]\nprot = 80"), &with_key),
+            Err(ConfigError::Parse { .. })
+        ));
+
+        assert!(matches!(
+            load(
+                None,
+                &[
+                    ("TMDB_API_KEY", "supersecret"),
+                    ("CORS_ALLOWED_ORIGINS", "example.com")
+                ]
+            ),
+            Err(ConfigError::Invalid {
+                setting: "cors.allowed_origins",
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            load(None, &[("TMDB_API_KEY", "  ")]),
+            Err(ConfigError::Invalid {
+                setting: "tmdb.api_key",
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            load(None, &[("TMDB_API_KEY", "supersecret"), ("WORKERS", "0")]),
+            Err(ConfigError::Invalid {
+                setting: "server.workers",
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            load(Some("[feedback]\nkeyword_decay = 1.5"), &with_key),
+            Err(ConfigError::Invalid {
+                setting: "feedback.keyword_decay",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_config_missing_api_key_file() {
+        let mut config = AppConfig::from_sources("app.toml", None, |name| match name {
+            "TMDB_API_KEY_FILE" => Some(String::from("does/not/exist.key")),
+            _ => None,
+        })
+        .unwrap();
+
+        let err = config.resolve_api_key().unwrap_err();
+
+        assert!(err.to_string().contains("does/not/exist.key"));
+    }
+}
@@ -1,29 +1,520 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[allow(unused_imports)]
 #[macro_use]
 extern crate lazy_static;
 
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
 use reqwest::{
-    header::{ACCEPT, AUTHORIZATION, USER_AGENT},
-    Response,
+    header::{ACCEPT, AUTHORIZATION, RETRY_AFTER},
+    Response, StatusCode,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub mod catalog;
+pub mod config;
+pub mod ranking;
+pub mod tmdb_cache;
+pub use catalog::{InMemoryCatalog, MovieCatalog};
+pub use config::{AppConfig, ConfigError};
+pub use tmdb_cache::{CacheKind, CacheStats, CacheTtls, TmdbCache};
+
+/* Maximum number of characters of a response body kept on a decode error */
+const BODY_SNIPPET_LENGTH: usize = 200;
+
+/* Watch region used when a session hasn't picked one */
+pub const DEFAULT_REGION: &str = "US";
+
+/* Language used when neither the session nor the request picked one */
+pub const DEFAULT_LANGUAGE: &str = "en-US";
+
+/* Errors returned by every call made through the Tmdb client */
+#[derive(Debug)]
+pub enum TmdbError {
+    /* The request never got a response - DNS, refused connections, dropped bodies, etc. */
+    Transport(reqwest::Error),
+    /* TMDB responded with a non-success status, status_message is taken from TMDB's error body */
+    Http {
+        status: u16,
+        status_message: Option<String>,
+    },
+    /* TMDB responded with 429 - retry_after is the number of seconds TMDB asked us to wait */
+    RateLimited {
+        retry_after: Option<u64>,
+    },
+    /* The response body didn't match the shape we expected */
+    Decode {
+        error: serde_json::Error,
+        body_snippet: String,
+    },
+    /* TMDB responded with 404 for the requested resource */
+    NotFound {
+        status_message: Option<String>,
+    },
+}
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+impl fmt::Display for TmdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TmdbError::Transport(err) => write!(f, "Error calling TMDB: {}", err),
+            TmdbError::Http {
+                status,
+                status_message,
+            } => write!(
+                f,
+                "TMDB responded with status {}: {}",
+                status,
+                status_message.as_deref().unwrap_or("no status message")
+            ),
+            TmdbError::RateLimited { retry_after } => match retry_after {
+                Some(seconds) => write!(f, "Rate limited by TMDB, retry after {}s", seconds),
+                None => write!(f, "Rate limited by TMDB"),
+            },
+            TmdbError::Decode {
+                error,
+                body_snippet,
+            } => write!(
+                f,
+                "Error parsing TMDB response: {} (body: {})",
+                error, body_snippet
+            ),
+            TmdbError::NotFound { status_message } => write!(
+                f,
+                "Not found on TMDB: {}",
+                status_message.as_deref().unwrap_or("no status message")
+            ),
+        }
+    }
+}
+
+impl TmdbError {
+    /* Whether trying the same request again could reasonably succeed */
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            TmdbError::Transport(err) => err.is_timeout() || err.is_connect(),
+            TmdbError::RateLimited { .. } => true,
+            TmdbError::Http { status, .. } => matches!(status, 500 | 502 | 503 | 504),
+            TmdbError::Decode { .. } | TmdbError::NotFound { .. } => false,
+        }
+    }
+}
+
+impl std::error::Error for TmdbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TmdbError::Transport(err) => Some(err),
+            TmdbError::Decode { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for TmdbError {
+    fn from(err: reqwest::Error) -> Self {
+        TmdbError::Transport(err)
+    }
+}
+
+/* Error body TMDB sends along with non-success statuses */
+#[derive(Debug, Deserialize)]
+struct TmdbStatusResponse {
+    status_message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 pub struct RecommendationCriteria {
     pub genres: Option<Vec<Genre>>,
     pub watch_providers: Option<Vec<WatchProvider>>,
     pub runtime: Option<Runtime>,
     pub decade: Option<Decade>,
     pub feedback: Option<Feedback>,
+    pub region: Option<String>,
+    pub monetization_types: Option<Vec<MonetizationType>>,
+    pub with_cast: Option<Vec<Person>>,
+    pub with_crew: Option<Vec<Person>>,
+    pub media_type: Option<MediaType>,
+    pub language: Option<String>,
+    pub quality: Option<Quality>,
+    pub vote_bounds: Option<VoteBounds>,
+    pub sort: Option<SortBy>,
+    // Kept in redis apart from the rest of the criteria (see redis_helper::mark_seen), so it's never serialized
+    #[serde(skip)]
+    pub seen: Option<SeenTitles>,
+    pub diversity: Option<f64>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+impl RecommendationCriteria {
+    /* Language titles, overviews and genre names come back in - falls back to US English */
+    pub fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or(DEFAULT_LANGUAGE)
+    }
+
+    /* Whether the session is after movies or shows - falls back to movies */
+    pub fn media_type(&self) -> MediaType {
+        self.media_type.unwrap_or_default()
+    }
+
+    /* ISO 3166-1 country code used for watch providers - falls back to the US */
+    pub fn region(&self) -> &str {
+        self.region.as_deref().unwrap_or(DEFAULT_REGION)
+    }
+
+    /* Explicit vote bounds on top of the quality preset's - no bounds at all if neither has been picked */
+    /* Sorting by rating adds a vote count floor if nothing else has set one, and it doesn't go above the maximum */
+    pub fn vote_bounds(&self) -> VoteBounds {
+        let preset = self.quality.map(|q| q.vote_bounds()).unwrap_or_default();
+        let mut bounds = self.vote_bounds.unwrap_or_default().or(preset);
+
+        if self.sort() == SortBy::Rating
+            && bounds.min_vote_count.is_none()
+            && bounds
+                .max_vote_count
+                .is_none_or(|max| max >= RATING_SORT_MIN_VOTE_COUNT)
+        {
+            bounds.min_vote_count = Some(RATING_SORT_MIN_VOTE_COUNT);
+        }
+
+        bounds
+    }
+
+    /* Order recommendations come back in - falls back to best match first */
+    pub fn sort(&self) -> SortBy {
+        self.sort.unwrap_or_default()
+    }
+
+    /* How far a batch is spread out across franchises and subgenres, from 0 (not at all) to 1 - see ranking::diversify */
+    /* Off unless the session has asked for it, since it looks up keywords and details for a whole candidate pool */
+    pub fn diversity(&self) -> f64 {
+        self.diversity.unwrap_or_default().clamp(0.0, 1.0)
+    }
+
+    /* Ways the user is willing to watch something - falls back to subscriptions only */
+    pub fn monetization_types(&self) -> Vec<MonetizationType> {
+        match &self.monetization_types {
+            Some(types) if !types.is_empty() => types.clone(),
+            _ => vec![MonetizationType::Flatrate],
+        }
+    }
+
+    /* IDs of the given media type the session has been recommended or voted on */
+    pub fn seen_ids(&self, media_type: MediaType) -> HashSet<i64> {
+        match &self.seen {
+            Some(seen) => seen.ids(media_type).clone(),
+            None => HashSet::new(),
+        }
+    }
+
+    pub fn mark_seen(&mut self, media_type: MediaType, ids: impl IntoIterator<Item = i64>) {
+        self.seen
+            .get_or_insert_with(SeenTitles::default)
+            .insert(media_type, ids);
+    }
+}
+
+/* Movies and shows a session has already been recommended or voted on, so they aren't recommended again */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
+pub struct SeenTitles {
+    #[serde(default)]
+    pub movies: HashSet<i64>,
+    #[serde(default)]
+    pub tv: HashSet<i64>,
+}
+
+impl SeenTitles {
+    /* Kept apart because TMDB numbers movies and shows separately */
+    pub fn ids(&self, media_type: MediaType) -> &HashSet<i64> {
+        match media_type {
+            MediaType::Movie => &self.movies,
+            MediaType::Tv => &self.tv,
+        }
+    }
+
+    pub fn insert(&mut self, media_type: MediaType, ids: impl IntoIterator<Item = i64>) {
+        match media_type {
+            MediaType::Movie => self.movies.extend(ids),
+            MediaType::Tv => self.tv.extend(ids),
+        }
+    }
+}
+
+/* Normalizes a user supplied ISO 3166-1 alpha-2 country code, returning None if it isn't one */
+pub fn normalize_region(region: &str) -> Option<String> {
+    let region = region.trim();
+
+    match region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => Some(region.to_ascii_uppercase()),
+        false => None,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegionResponse {
+    pub region: String,
+}
+
+/* Normalizes a user supplied language tag into the form TMDB expects, returning None if it isn't one */
+/* Accepts an ISO 639-1 language with an optional ISO 3166-1 country - fr, fr-ca and fr_CA all work */
+pub fn normalize_language(language: &str) -> Option<String> {
+    let mut parts = language.trim().split(['-', '_']);
+
+    let is_code = |part: &str| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(lang), None, None) if is_code(lang) => Some(lang.to_ascii_lowercase()),
+        (Some(lang), Some(country), None) if is_code(lang) && is_code(country) => Some(format!(
+            "{}-{}",
+            lang.to_ascii_lowercase(),
+            country.to_ascii_uppercase()
+        )),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LanguageResponse {
+    pub language: String,
+}
+
+/* What a session is recommending - TMDB keeps movies and TV under separate endpoints with separate IDs */
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    #[default]
+    Movie,
+    Tv,
+}
+
+impl MediaType {
+    /* Path segment TMDB uses for this media type */
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Movie => "movie",
+            MediaType::Tv => "tv",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MediaTypeResponse {
+    pub media_type: MediaType,
+}
+
+/* Order discover results are ranked in - relevance re-ranks popular results against the session's */
+/* preferences (see ranking) and random shuffles them, instead of asking TMDB to sort */
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Relevance,
+    Popularity,
+    Rating,
+    ReleaseDate,
+    Revenue,
+    Random,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SortResponse {
+    pub sort: SortBy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SortInfo {
+    sort: SortBy,
+    name: String,
+}
+
+impl SortBy {
+    pub fn info(&self) -> SortInfo {
+        let name = match self {
+            SortBy::Relevance => "Best match",
+            SortBy::Popularity => "Most popular",
+            SortBy::Rating => "Highest rated",
+            SortBy::ReleaseDate => "Newest first",
+            SortBy::Revenue => "Biggest box office",
+            SortBy::Random => "Surprise me",
+        };
+
+        SortInfo {
+            sort: *self,
+            name: String::from(name),
+        }
+    }
+
+    /* sort_by value for discover - TV has no revenue, so that falls back to popularity */
+    pub fn discover_param(&self, media_type: MediaType) -> &'static str {
+        match (self, media_type) {
+            (SortBy::Rating, _) => "vote_average.desc",
+            (SortBy::ReleaseDate, MediaType::Movie) => "primary_release_date.desc",
+            (SortBy::ReleaseDate, MediaType::Tv) => "first_air_date.desc",
+            (SortBy::Revenue, MediaType::Movie) => "revenue.desc",
+            (SortBy::Relevance, _)
+            | (SortBy::Popularity, _)
+            | (SortBy::Revenue, MediaType::Tv)
+            | (SortBy::Random, _) => "popularity.desc",
+        }
+    }
+}
+
+/* Sorting by rating with no vote count floor surfaces obscure titles with a single 10/10 vote */
+pub const RATING_SORT_MIN_VOTE_COUNT: i64 = 200;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiversityResponse {
+    pub diversity: f64,
+}
+
+/* like and dislike are the keyword IDs discover filters on, derived from keyword_profile */
+/* must_have and avoid are picked by the user directly */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 pub struct Feedback {
     pub like: Option<Vec<i64>>,
     pub dislike: Option<Vec<i64>>,
+    pub must_have: Option<Vec<Keyword>>,
+    pub avoid: Option<Vec<Keyword>>,
+    #[serde(default)]
+    pub keyword_profile: Option<KeywordProfile>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeywordPreference {
+    MustHave,
+    Avoid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeywordPreferenceResponse {
+    pub keyword: Keyword,
+    pub preference: KeywordPreference,
+}
+
+impl Feedback {
+    /* Merges a round of net keyword votes into the profile, then re-derives like and dislike from it */
+    pub fn record_votes(&mut self, votes: &HashMap<i64, f64>, decay: f64) {
+        let mut profile = self
+            .keyword_profile
+            .take()
+            .unwrap_or_else(|| KeywordProfile::from_ids(&self.like, &self.dislike));
+        profile.record(votes, decay);
+
+        let non_empty = |ids: Vec<i64>| match ids.is_empty() {
+            true => None,
+            false => Some(ids),
+        };
+
+        self.like = non_empty(profile.liked(PROFILE_DISCOVER_KEYWORDS));
+        self.dislike = non_empty(profile.disliked(PROFILE_DISCOVER_KEYWORDS));
+        self.keyword_profile = Some(profile);
+    }
+
+    /* Adds a keyword to must_have or avoid - a keyword can only be in one of them, so it's taken out of the other */
+    pub fn add_keyword_preference(&mut self, keyword: Keyword, preference: KeywordPreference) {
+        self.remove_keyword_preference(keyword.id);
+
+        let keywords = match preference {
+            KeywordPreference::MustHave => self.must_have.get_or_insert_with(Vec::new),
+            KeywordPreference::Avoid => self.avoid.get_or_insert_with(Vec::new),
+        };
+
+        keywords.push(keyword);
+    }
+
+    /* Returns whether the keyword was in either list */
+    pub fn remove_keyword_preference(&mut self, keyword_id: i64) -> bool {
+        let mut removed = false;
+
+        for keywords in [&mut self.must_have, &mut self.avoid] {
+            if let Some(list) = keywords {
+                let before = list.len();
+                list.retain(|k| k.id != keyword_id);
+                removed |= list.len() != before;
+
+                if list.is_empty() {
+                    *keywords = None;
+                }
+            }
+        }
+
+        removed
+    }
+}
+
+/* Number of the profile's most liked and most disliked keywords passed to discover */
+pub const PROFILE_DISCOVER_KEYWORDS: usize = 5;
+
+/* Weights that decay below this are dropped, so the profile doesn't grow forever */
+const MIN_KEYWORD_WEIGHT: f64 = 0.05;
+
+/* Net votes per keyword ID built up over every round of feedback - positive is liked, negative disliked */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct KeywordProfile {
+    pub weights: HashMap<i64, f64>,
+    /* Rounds of feedback merged in so far */
+    pub rounds: u32,
+}
+
+impl KeywordProfile {
+    /* Sessions from before the profile was kept only have like and dislike, which count as a single vote each */
+    pub fn from_ids(like: &Option<Vec<i64>>, dislike: &Option<Vec<i64>>) -> Self {
+        let mut weights: HashMap<i64, f64> = HashMap::new();
+
+        for (ids, vote) in [(like, 1.0), (dislike, -1.0)] {
+            for id in ids.iter().flatten() {
+                *weights.entry(*id).or_default() += vote;
+            }
+        }
+
+        Self { weights, rounds: 0 }
+    }
+
+    /* Existing weights are scaled by decay before the new votes are added - 1.0 never forgets, 0.5 halves older votes each round */
+    pub fn record(&mut self, votes: &HashMap<i64, f64>, decay: f64) {
+        for weight in self.weights.values_mut() {
+            *weight *= decay;
+        }
+
+        for (id, vote) in votes {
+            *self.weights.entry(*id).or_default() += vote;
+        }
+
+        self.weights
+            .retain(|_, weight| weight.abs() >= MIN_KEYWORD_WEIGHT);
+        self.rounds += 1;
+    }
+
+    /* Up to count of the most liked keywords, strongest first - ties go to the lower ID so discover queries are stable */
+    pub fn liked(&self, count: usize) -> Vec<i64> {
+        self.strongest(count, 1.0)
+    }
+
+    /* Up to count of the most disliked keywords, strongest first */
+    pub fn disliked(&self, count: usize) -> Vec<i64> {
+        self.strongest(count, -1.0)
+    }
+
+    fn strongest(&self, count: usize, sign: f64) -> Vec<i64> {
+        let mut keywords: Vec<(i64, f64)> = self
+            .weights
+            .iter()
+            .map(|(id, weight)| (*id, weight * sign))
+            .filter(|(_, weight)| *weight > 0.0)
+            .collect();
+
+        keywords.sort_by(|(a_id, a), (b_id, b)| {
+            b.partial_cmp(a)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a_id.cmp(b_id))
+        });
+
+        keywords.into_iter().take(count).map(|(id, _)| id).collect()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
@@ -32,9 +523,51 @@ pub struct Keyword {
     pub name: String,
 }
 
+/* An actor, director, writer etc - TMDB uses the same IDs for cast and crew */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Person {
+    pub id: i64,
+    pub name: String,
+    pub known_for_department: Option<String>,
+    pub profile_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SearchPeopleResponse {
+    pub results: Vec<Person>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct CastMember {
+    pub id: i64,
+    pub name: String,
+    pub character: Option<String>,
+    /* Billing order - lower is more prominent */
+    pub order: i32,
+    pub profile_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct CrewMember {
+    pub id: i64,
+    pub name: String,
+    pub department: String,
+    pub job: String,
+    pub profile_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct CreditsResponse {
+    pub id: i64,
+    pub cast: Vec<CastMember>,
+    pub crew: Vec<CrewMember>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct KeywordResponse {
     pub id: i64,
+    /* TV keyword responses call this results */
+    #[serde(alias = "results")]
     pub keywords: Vec<Keyword>,
 }
 
@@ -96,6 +629,22 @@ impl Runtime {
         }
     }
 
+    /* Same options scaled down to a single TV episode */
+    pub fn episode_runtime(&self) -> (i32, i32) {
+        match self {
+            Runtime::Quick => (0, 25),
+            Runtime::Average => (25, 45),
+            Runtime::MovieNight => (45, 65),
+            Runtime::MartinScorsese => (65, 500),
+        }
+    }
+
+    /* Whether a movie of this many minutes falls in the range - inclusive like discover's runtime filter */
+    pub fn includes(&self, minutes: i32) -> bool {
+        let (min, max) = self.runtime();
+        minutes >= min && minutes <= max
+    }
+
     pub fn from_string(runtime_string: &str) -> Self {
         match runtime_string {
             "Quick" => Runtime::Quick,
@@ -197,627 +746,3205 @@ impl Decade {
             Decade::Recent => (String::from("2020"), String::from("2024")),
         }
     }
-}
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
-pub struct Movie {
-    pub id: i64,
-    pub overview: String,
-    //popularity: f64,
-    pub poster_path: Option<String>,
-    pub release_date: String,
-    pub title: String,
-    //vote_average: f64,
-    //vote_count: i64,
-}
+    /* Whether a YYYY-MM-DD release or air date falls in the decade - blank dates never do */
+    pub fn contains_date(&self, date: &str) -> bool {
+        let (start_year, end_year) = self.year_range();
 
-#[derive(Debug, Deserialize)]
-pub struct SearchByTitleResponse {
-    pub results: Vec<Movie>,
+        match date.get(0..4) {
+            Some(year) => year >= start_year.as_str() && year <= end_year.as_str(),
+            None => false,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
-pub struct WatchProvider {
-    pub logo_path: String,
-    pub provider_id: i32,
-    pub provider_name: String,
+/*
+   Quality presets
+*/
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    Acclaimed,
+    HiddenGems,
+    Anything,
 }
 
-/* Represents a JSON object of a country/region - contains a list of movie providers broken down by type: */
-/* flatrate - subscription based services like Netflix, HBO, etc. */
-/* buy - services where movies can be bought like Vudu, Google Play Movies, etc */
-/* rent - services where movies can be rented, like Vudu, Google Play Movies, etc */
-#[derive(Debug, Deserialize)]
-pub struct WatchProviderRegion {
-    pub flatrate: Vec<WatchProvider>,
-    //buy: Vec<WatchProvider>,
-    //rent: Vec<WatchProvider>,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualityResponse {
+    pub quality: Quality,
 }
 
-/* Represents a JSON object containing supported countries/regions */
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "UPPERCASE")]
-pub struct WatchProviderRegions {
-    pub us: WatchProviderRegion,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualityInfo {
+    quality: Quality,
+    name: String,
+    description: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GetWatchProvidersResponse {
-    pub results: WatchProviderRegions,
-}
+impl Quality {
+    pub fn info(&self) -> QualityInfo {
+        match self {
+            Quality::Acclaimed => QualityInfo {
+                quality: *self,
+                name: String::from("Acclaimed"),
+                description: String::from("Crowd-pleasers that critics and audiences agree on"),
+            },
+            Quality::HiddenGems => QualityInfo {
+                quality: *self,
+                name: String::from("Hidden Gems"),
+                description: String::from("Well-loved movies that not many people have found yet"),
+            },
+            Quality::Anything => QualityInfo {
+                quality: *self,
+                name: String::from("Anything"),
+                description: String::from("No quality bar, just what's popular"),
+            },
+        }
+    }
 
-#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
-pub struct Genre {
-    pub id: i32,
-    pub name: String,
+    /* Vote bounds the preset stands for when passed to discover */
+    pub fn vote_bounds(&self) -> VoteBounds {
+        match self {
+            Quality::Acclaimed => VoteBounds {
+                min_vote_average: Some(7.5),
+                min_vote_count: Some(1000),
+                ..VoteBounds::default()
+            },
+            Quality::HiddenGems => VoteBounds {
+                min_vote_average: Some(7.0),
+                min_vote_count: Some(50),
+                max_vote_count: Some(1000),
+                ..VoteBounds::default()
+            },
+            Quality::Anything => VoteBounds::default(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GetGenresResponse {
-    pub genres: Vec<Genre>,
+/* Inclusive bounds on TMDB's 0-10 vote average and on the number of votes behind it - unset bounds aren't filtered on */
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+pub struct VoteBounds {
+    pub min_vote_average: Option<f64>,
+    pub max_vote_average: Option<f64>,
+    pub min_vote_count: Option<i64>,
+    pub max_vote_count: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GetProvidersResponse {
-    pub results: Vec<WatchProvider>,
-}
+impl VoteBounds {
+    /* Averages have to be between 0 and 10, counts can't be negative and no minimum can be above its maximum */
+    pub fn is_valid(&self) -> bool {
+        let average_ok = |average: Option<f64>| average.is_none_or(|a| (0.0..=10.0).contains(&a));
+        let count_ok = |count: Option<i64>| count.is_none_or(|c| c >= 0);
+
+        average_ok(self.min_vote_average)
+            && average_ok(self.max_vote_average)
+            && count_ok(self.min_vote_count)
+            && count_ok(self.max_vote_count)
+            && match (self.min_vote_average, self.max_vote_average) {
+                (Some(min), Some(max)) => min <= max,
+                _ => true,
+            }
+            && match (self.min_vote_count, self.max_vote_count) {
+                (Some(min), Some(max)) => min <= max,
+                _ => true,
+            }
+    }
 
-#[derive(Debug, Deserialize)]
-pub struct GetRecommendationsResponse {
-    pub results: Vec<Movie>,
-}
+    /* Bounds set here win a range at a time - setting either end of the average (or count) replaces fallback's */
+    /* whole range, so merging two valid bounds can't leave a minimum above a maximum */
+    pub fn or(self, fallback: VoteBounds) -> VoteBounds {
+        let (min_vote_average, max_vote_average) =
+            match (self.min_vote_average, self.max_vote_average) {
+                (None, None) => (fallback.min_vote_average, fallback.max_vote_average),
+                explicit => explicit,
+            };
+        let (min_vote_count, max_vote_count) = match (self.min_vote_count, self.max_vote_count) {
+            (None, None) => (fallback.min_vote_count, fallback.max_vote_count),
+            explicit => explicit,
+        };
 
-pub struct AsyncRecommendation {
-    pub movie: Movie,
-    //pub providers: Vec<WatchProvider>,
-    pub async_providers: tokio::task::JoinHandle<GetWatchProvidersResponse>,
+        VoteBounds {
+            min_vote_average,
+            max_vote_average,
+            min_vote_count,
+            max_vote_count,
+        }
+    }
+
+    pub fn contains(&self, vote_average: f64, vote_count: i64) -> bool {
+        self.min_vote_average.is_none_or(|min| vote_average >= min)
+            && self.max_vote_average.is_none_or(|max| vote_average <= max)
+            && self.min_vote_count.is_none_or(|min| vote_count >= min)
+            && self.max_vote_count.is_none_or(|max| vote_count <= max)
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct MovieRecommendation {
-    pub movie: Movie,
-    pub providers: Vec<WatchProvider>,
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct Movie {
+    pub id: i64,
+    pub overview: String,
+    #[serde(default)]
+    pub popularity: f64,
+    pub poster_path: Option<String>,
+    /* Absolute URL, filled in by ResolveImages */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster_url: Option<String>,
+    pub release_date: String,
+    pub title: String,
+    #[serde(default)]
+    pub vote_average: f64,
+    #[serde(default)]
+    pub vote_count: i64,
+    #[serde(default)]
+    pub genre_ids: Vec<i32>,
 }
 
-/* Struct for interacting with TMDB API */
-#[derive(Clone)]
-pub struct Tmdb {
-    base_url: String,
-    api_key: String,
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct SpokenLanguage {
+    pub iso_639_1: String,
+    pub english_name: String,
+    pub name: String,
 }
 
-/* Methods for TMDB API endpoints */
-impl Tmdb {
-    /* Constructor for building Tmdb object */
-    pub fn new() -> Self {
-        let api_key: String = fs::read_to_string("config/api.key")
-            .expect("Unable to read API Key!")
-            .trim()
-            .to_string();
-        let base_url: String = String::from("https://api.themoviedb.org/3");
-        Self { api_key, base_url }
-    }
+/* Everything TMDB knows about a single movie - enough to render a detail page */
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct MovieDetails {
+    pub id: i64,
+    pub title: String,
+    pub overview: String,
+    pub tagline: Option<String>,
+    /* In minutes - TMDB leaves this empty for unreleased movies */
+    pub runtime: Option<i32>,
+    pub genres: Vec<Genre>,
+    pub vote_average: f64,
+    pub vote_count: i64,
+    pub budget: i64,
+    pub imdb_id: Option<String>,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
+    /* Absolute URL, filled in by ResolveImages */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backdrop_url: Option<String>,
+    pub release_date: String,
+    #[serde(default)]
+    pub spoken_languages: Vec<SpokenLanguage>,
+    /* Set when TMDB groups the movie into a franchise with its sequels */
+    #[serde(default)]
+    pub belongs_to_collection: Option<Collection>,
+}
 
-    pub fn mock(api_key: String, base_url: String) -> Self {
-        Self { api_key, base_url }
-    }
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
+}
 
-    pub fn mock_shared_instance(api_key: String, base_url: String) -> Arc<Self> {
-        Arc::new(Self::mock(api_key, base_url))
+/* A TV series as it shows up in discover results */
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct TvShow {
+    pub id: i64,
+    pub name: String,
+    pub overview: String,
+    pub poster_path: Option<String>,
+    /* Absolute URL, filled in by ResolveImages */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster_url: Option<String>,
+    /* Empty for shows that haven't aired yet */
+    #[serde(default)]
+    pub first_air_date: String,
+    #[serde(default)]
+    pub genre_ids: Vec<i32>,
+}
+
+/* Everything TMDB knows about a single series */
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct TvDetails {
+    pub id: i64,
+    pub name: String,
+    pub overview: String,
+    pub tagline: Option<String>,
+    /* In minutes - shows with uneven episodes list more than one */
+    #[serde(default)]
+    pub episode_run_time: Vec<i32>,
+    pub number_of_seasons: i32,
+    pub number_of_episodes: i32,
+    pub first_air_date: Option<String>,
+    pub last_air_date: Option<String>,
+    pub in_production: bool,
+    pub genres: Vec<Genre>,
+    pub vote_average: f64,
+    pub vote_count: i64,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
+    /* Absolute URL, filled in by ResolveImages */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backdrop_url: Option<String>,
+    #[serde(default)]
+    pub spoken_languages: Vec<SpokenLanguage>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+pub struct WatchProvider {
+    pub logo_path: String,
+    /* Absolute URL, filled in by ResolveImages */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo_url: Option<String>,
+    pub provider_id: i32,
+    pub provider_name: String,
+}
+
+/*
+    Ways a movie can be watched - these match TMDB's monetization type names
+*/
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MonetizationType {
+    Flatrate,
+    Free,
+    Ads,
+    Rent,
+    Buy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MonetizationTypeInfo {
+    monetization_type: MonetizationType,
+    name: String,
+    description: String,
+}
+
+impl MonetizationType {
+    pub fn info(&self) -> MonetizationTypeInfo {
+        let (name, description) = match self {
+            MonetizationType::Flatrate => ("Subscription", "Included with your streaming services"),
+            MonetizationType::Free => ("Free", "Free to watch, no strings attached"),
+            MonetizationType::Ads => (
+                "Free with ads",
+                "Free to watch if you can sit through a few ads",
+            ),
+            MonetizationType::Rent => ("Rent", "Available to rent for a few bucks"),
+            MonetizationType::Buy => ("Buy", "Available to buy and keep"),
+        };
+
+        MonetizationTypeInfo {
+            monetization_type: *self,
+            name: String::from(name),
+            description: String::from(description),
+        }
     }
 
-    /* For building shared instance */
-    pub fn shared_instance() -> Arc<Self> {
-        Arc::new(Self::new())
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MonetizationType::Flatrate => "flatrate",
+            MonetizationType::Free => "free",
+            MonetizationType::Ads => "ads",
+            MonetizationType::Rent => "rent",
+            MonetizationType::Buy => "buy",
+        }
     }
+}
 
-    /* Private function to make TMDB API call */
-    async fn make_tmdb_request(&self, url: &String) -> Result<Response, reqwest::Error> {
-        let client = reqwest::Client::new();
-        client
-            .get(format!("{}/{}", self.base_url, url))
-            .header(AUTHORIZATION, format!("Bearer {0}", self.api_key))
-            .header(ACCEPT, "application/json")
-            .header(USER_AGENT, "rust web-api demo")
-            .send()
-            .await
+/* Represents a JSON object of a country/region - contains a list of movie providers broken down by type: */
+/* flatrate - subscription based services like Netflix, HBO, etc. */
+/* free - services where movies are free to watch, like Kanopy, Plex, etc */
+/* ads - services where movies are free with ads, like Tubi, Pluto TV, etc */
+/* buy - services where movies can be bought like Vudu, Google Play Movies, etc */
+/* rent - services where movies can be rented, like Vudu, Google Play Movies, etc */
+/* TMDB leaves out any type that has no providers in the region */
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct WatchProviderRegion {
+    #[serde(default)]
+    pub flatrate: Vec<WatchProvider>,
+    #[serde(default)]
+    pub free: Vec<WatchProvider>,
+    #[serde(default)]
+    pub ads: Vec<WatchProvider>,
+    #[serde(default)]
+    pub rent: Vec<WatchProvider>,
+    #[serde(default)]
+    pub buy: Vec<WatchProvider>,
+}
+
+impl WatchProviderRegion {
+    pub fn providers(&self, monetization_type: MonetizationType) -> &Vec<WatchProvider> {
+        match monetization_type {
+            MonetizationType::Flatrate => &self.flatrate,
+            MonetizationType::Free => &self.free,
+            MonetizationType::Ads => &self.ads,
+            MonetizationType::Rent => &self.rent,
+            MonetizationType::Buy => &self.buy,
+        }
     }
 
-    /* Searches for movie by title - helpful for retrieving movie IDs */
-    pub async fn search_by_title(
-        &self,
-        movie_title: &String,
-    ) -> Result<SearchByTitleResponse, Box<dyn std::error::Error>> {
-        let url = format!("{}/search/movie?query={}", self.base_url, movie_title);
+    /* Copy of the region keeping only the given monetization types */
+    pub fn only(&self, monetization_types: &[MonetizationType]) -> WatchProviderRegion {
+        let keep = |monetization_type: MonetizationType| match monetization_types
+            .contains(&monetization_type)
+        {
+            true => self.providers(monetization_type).clone(),
+            false => vec![],
+        };
 
-        let search_response = self.make_tmdb_request(&url).await?;
+        WatchProviderRegion {
+            flatrate: keep(MonetizationType::Flatrate),
+            free: keep(MonetizationType::Free),
+            ads: keep(MonetizationType::Ads),
+            rent: keep(MonetizationType::Rent),
+            buy: keep(MonetizationType::Buy),
+        }
+    }
 
-        let movie_results = search_response.json::<SearchByTitleResponse>().await?;
+    /* Which of the given providers show up under any monetization type, each listed once */
+    pub fn matching(&self, watch_providers: &[WatchProvider]) -> Vec<WatchProvider> {
+        let mut matching: Vec<WatchProvider> = vec![];
+
+        for offered in [&self.flatrate, &self.free, &self.ads, &self.rent, &self.buy] {
+            for provider in offered {
+                let picked = watch_providers
+                    .iter()
+                    .any(|w| w.provider_id == provider.provider_id);
+                let listed = matching
+                    .iter()
+                    .any(|m| m.provider_id == provider.provider_id);
+
+                if picked && !listed {
+                    matching.push(provider.clone());
+                }
+            }
+        }
 
-        Ok(movie_results)
+        matching
     }
 
-    pub async fn get_keywords_for_id(
-        &self,
-        movie_id: &i64,
-    ) -> Result<KeywordResponse, Box<dyn std::error::Error>> {
-        let url = format!("movie/{}/keywords", movie_id);
+    /* Whether any of the given providers shows up under any monetization type */
+    pub fn offers_any(&self, watch_providers: &[WatchProvider]) -> bool {
+        [&self.flatrate, &self.free, &self.ads, &self.rent, &self.buy]
+            .iter()
+            .any(|offered| {
+                offered.iter().any(|p| {
+                    watch_providers
+                        .iter()
+                        .any(|w| w.provider_id == p.provider_id)
+                })
+            })
+    }
+}
 
-        let keyword_response = self.make_tmdb_request(&url).await?;
+/* Represents a JSON object containing supported countries/regions, keyed by ISO 3166-1 country code */
+pub type WatchProviderRegions = HashMap<String, WatchProviderRegion>;
 
-        let keyword_results = keyword_response.json::<KeywordResponse>().await?;
+#[derive(Debug, Deserialize, Clone)]
+pub struct GetWatchProvidersResponse {
+    pub results: WatchProviderRegions,
+}
 
-        Ok(keyword_results)
+impl GetWatchProvidersResponse {
+    /* Subscription providers for a region - empty if the movie isn't available there */
+    pub fn flatrate_for_region(&self, region: &str) -> Vec<WatchProvider> {
+        self.results
+            .get(region)
+            .map(|providers| providers.flatrate.clone())
+            .unwrap_or_default()
     }
 
-    /* Gets watch providers by movie ID */
-    /* Watch providers are given by country, and by type: */
-    /* For this application we are mostly interested in "flatrate" */
-    pub async fn get_watch_providers_by_id(
+    /* Providers for a region grouped by monetization type, keeping only the requested types */
+    pub fn providers_for_region(
         &self,
-        movie_id: &String,
-    ) -> Result<GetWatchProvidersResponse, Box<dyn std::error::Error>> {
-        let url = format!("movie/{}/watch/providers", movie_id);
+        region: &str,
+        monetization_types: &[MonetizationType],
+    ) -> WatchProviderRegion {
+        self.results
+            .get(region)
+            .map(|providers| providers.only(monetization_types))
+            .unwrap_or_default()
+    }
+}
 
-        let provider_response = self.make_tmdb_request(&url).await?;
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+pub struct Genre {
+    pub id: i32,
+    pub name: String,
+}
 
-        // TODO: Improve error handling for things not available on streaming services
-        let providers = provider_response
-            .json::<GetWatchProvidersResponse>()
-            .await?;
-        Ok(providers)
-    }
+#[derive(Debug, Deserialize)]
+pub struct GetGenresResponse {
+    pub genres: Vec<Genre>,
+}
 
-    pub async fn get_genre_list(&self) -> Result<GetGenresResponse, Box<dyn std::error::Error>> {
-        let url = "genre/movie/list?language=en".to_string();
+#[derive(Debug, Deserialize)]
+pub struct GetProvidersResponse {
+    pub results: Vec<WatchProvider>,
+}
 
-        let genre_response = self.make_tmdb_request(&url).await?;
+/* A page of results - discover and search share the same shape for movies and TV */
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PagedResponse<T> {
+    pub page: u32,
+    pub results: Vec<T>,
+    pub total_pages: u32,
+    pub total_results: u32,
+}
 
-        let genres = genre_response.json::<GetGenresResponse>().await?;
+pub type GetRecommendationsResponse = PagedResponse<Movie>;
 
-        Ok(genres)
-    }
+pub type SearchByTitleResponse = PagedResponse<Movie>;
 
-    pub async fn get_providers_list(
-        &self,
-    ) -> Result<GetProvidersResponse, Box<dyn std::error::Error>> {
-        let url = "watch/providers/movie?language=en-US&watch_region=US".to_string();
+pub type SearchKeywordsResponse = PagedResponse<Keyword>;
 
-        let providers_response = self.make_tmdb_request(&url).await?;
+pub type GetTvRecommendationsResponse = PagedResponse<TvShow>;
 
-        let providers = providers_response
-            .json::<GetProvidersResponse>()
-            .await
-            .expect("Error parsing JSON");
+/*
+   Videos
+*/
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Video {
+    pub key: String,
+    pub name: String,
+    /* Where key is hosted - YouTube or Vimeo */
+    pub site: String,
+    /* Trailer, Teaser, Clip, Featurette and so on */
+    #[serde(rename = "type")]
+    pub video_type: String,
+    #[serde(default)]
+    pub official: bool,
+    /* Resolution, e.g. 1080 */
+    #[serde(default)]
+    pub size: i32,
+    /* ISO 639-1 code of the spoken language */
+    pub iso_639_1: String,
+    #[serde(default)]
+    pub published_at: String,
+}
 
-        Ok(providers)
-    }
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct VideosResponse {
+    pub id: i64,
+    pub results: Vec<Video>,
+}
 
-    pub async fn get_recommendations(
-        &self,
-        genres: Vec<Genre>,
-        watch_providers: Vec<WatchProvider>,
-        runtime: Runtime,
-        decade: Decade,
-        feedback: Option<Feedback>,
-    ) -> Result<GetRecommendationsResponse, Box<dyn std::error::Error>> {
-        let genre_ids: String = genres
-            .iter()
-            .map(|g| g.id.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    pub name: String,
+    pub key: String,
+    pub url: String,
+    pub language: String,
+}
+
+impl VideosResponse {
+    /* Best YouTube trailer for a language tag like fr-CA - official trailers in that language beat English ones, */
+    /* which beat anything else, and ties go to the sharpest then newest video. Teasers stand in if there's no trailer */
+    pub fn best_trailer(&self, language: &str) -> Option<Trailer> {
+        let language = language.split('-').next().unwrap_or(language);
+        let rank = |video: &Video| {
+            (
+                video.video_type == "Trailer",
+                video.official,
+                match video.iso_639_1.as_str() {
+                    code if code == language => 2,
+                    "en" => 1,
+                    _ => 0,
+                },
+                video.size,
+                video.published_at.clone(),
+            )
+        };
 
-        let provider_ids: String = watch_providers
+        self.results
             .iter()
-            .map(|p| p.provider_id.to_string())
-            .collect::<Vec<_>>()
-            .join("|");
+            .filter(|video| video.site == "YouTube")
+            .filter(|video| video.video_type == "Trailer" || video.video_type == "Teaser")
+            .max_by_key(|video| rank(video))
+            .map(|video| Trailer {
+                name: video.name.clone(),
+                key: video.key.clone(),
+                url: format!("https://www.youtube.com/watch?v={}", video.key),
+                language: video.iso_639_1.clone(),
+            })
+    }
+}
 
-        let start_date = decade.year_range().0;
+pub struct AsyncRecommendation {
+    pub movie: Movie,
+    //pub providers: Vec<WatchProvider>,
+    pub async_providers: tokio::task::JoinHandle<Result<WatchProviderRegion, TmdbError>>,
+    pub async_trailer: tokio::task::JoinHandle<Result<Option<Trailer>, TmdbError>>,
+    /* Only needed for the explanation */
+    pub keywords: AsyncKeywords,
+}
 
-        let end_date = decade.year_range().1;
+/* A movie's keywords - already loaded if the batch was ranked on them, otherwise still being looked up */
+pub enum AsyncKeywords {
+    Loaded(Vec<Keyword>),
+    Pending(tokio::task::JoinHandle<Result<KeywordResponse, TmdbError>>),
+}
 
-        let mut url = format!(
-            "discover/movie?include_adult=false&include_video=false&language=en-US&page=1&{}&{}&{}&{}&sort_by=popularity.desc&watch_region=US&{}&with_watch_monetization_types=flatrate&{}",
-            format!("primary_release_date.gte={}-01-01", start_date),
-            format!("primary_release_date.lte={}-12-31", end_date),
-            format!("with_runtime.gte={}", runtime.runtime().0),
-            format!("with_runtime.lte={}",runtime.runtime().1),
-            format!("with_genres={}", genre_ids),
-            format!("with_watch_providers={}",provider_ids)
-        );
+pub struct AsyncTvRecommendation {
+    pub show: TvShow,
+    pub async_providers: tokio::task::JoinHandle<Result<WatchProviderRegion, TmdbError>>,
+}
+
+/* providers only contains the monetization types the session asked for */
+#[derive(Debug, Serialize)]
+pub struct MovieRecommendation {
+    pub movie: Movie,
+    pub providers: WatchProviderRegion,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailer: Option<Trailer>,
+    pub explanation: Explanation,
+}
+
+/* Why a movie was recommended, in terms of what the session picked - anything that didn't play a part is left empty */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct Explanation {
+    pub genres: Vec<Genre>,
+    /* Keywords the session liked or must have, by name */
+    pub keywords: Vec<Keyword>,
+    /* Picked providers the movie is on - one is enough for it to be recommended */
+    pub providers: Vec<WatchProvider>,
+    pub decade: Option<Decade>,
+    pub runtime: Option<Runtime>,
+}
+
+impl Explanation {
+    /* keywords are the movie's own, providers what it's offered on in the session's region */
+    /* minutes comes from the movie's details, since list results don't carry a runtime - without it the runtime isn't a reason */
+    pub fn new(
+        criteria: &RecommendationCriteria,
+        movie: &Movie,
+        keywords: &[Keyword],
+        providers: &WatchProviderRegion,
+        minutes: Option<i32>,
+    ) -> Self {
+        let genres = criteria
+            .genres
+            .iter()
+            .flatten()
+            .filter(|genre| movie.genre_ids.contains(&genre.id))
+            .cloned()
+            .collect();
 
-        match feedback {
+        let liked_ids: HashSet<i64> = match &criteria.feedback {
             Some(feedback) => {
-                match feedback.like {
-                    Some(keywords) => url.push_str(&format!(
-                        "&with_keywords={}",
-                        keywords
-                            .iter()
-                            .map(|k| k.to_string())
-                            .collect::<Vec<_>>()
-                            .join("|")
-                    )),
-                    None => println!("Nothing"),
+                let learned: Vec<i64> = match &feedback.keyword_profile {
+                    Some(profile) => profile.liked(profile.weights.len()),
+                    None => feedback.like.clone().unwrap_or_default(),
                 };
 
-                match feedback.dislike {
-                    Some(keywords) => url.push_str(&format!(
-                        "&without_keywords={}",
-                        keywords
+                learned
+                    .into_iter()
+                    .chain(
+                        feedback
+                            .must_have
                             .iter()
-                            .map(|k| k.to_string())
-                            .collect::<Vec<_>>()
-                            .join("|")
-                    )),
-                    None => println!("Nothing"),
-                };
+                            .flatten()
+                            .map(|keyword| keyword.id),
+                    )
+                    .collect()
             }
-            None => {}
+            None => HashSet::new(),
+        };
+
+        let keywords = keywords
+            .iter()
+            .filter(|keyword| liked_ids.contains(&keyword.id))
+            .cloned()
+            .collect();
+
+        let providers = match &criteria.watch_providers {
+            Some(watch_providers) => providers.matching(watch_providers),
+            None => vec![],
+        };
+
+        Self {
+            genres,
+            keywords,
+            providers,
+            decade: criteria
+                .decade
+                .clone()
+                .filter(|decade| decade.contains_date(&movie.release_date)),
+            runtime: criteria
+                .runtime
+                .clone()
+                .filter(|runtime| minutes.is_some_and(|minutes| runtime.includes(minutes))),
         }
+    }
+}
 
-        println!("{}", &url);
+#[derive(Debug, Serialize)]
+pub struct TvRecommendation {
+    pub show: TvShow,
+    pub providers: WatchProviderRegion,
+}
 
-        let recommendation_response = self.make_tmdb_request(&url).await?;
+/*
+   Images
+*/
+/* Widths used for absolute image URLs in responses */
+pub const POSTER_WIDTH: u32 = 342;
+pub const BACKDROP_WIDTH: u32 = 1280;
+pub const LOGO_WIDTH: u32 = 92;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    Poster,
+    Backdrop,
+    Logo,
+}
 
-        let recommendations = recommendation_response
-            .json::<GetRecommendationsResponse>()
-            .await
-            .expect("Error parsing JSON");
+/* Where TMDB hosts images and the sizes each kind of image comes in, from the images block of /configuration */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ImageConfiguration {
+    pub secure_base_url: String,
+    pub backdrop_sizes: Vec<String>,
+    pub logo_sizes: Vec<String>,
+    pub poster_sizes: Vec<String>,
+}
 
-        Ok(recommendations)
+/* TMDB's long standing values, for when /configuration can't be reached */
+impl Default for ImageConfiguration {
+    fn default() -> Self {
+        let sizes = |sizes: &[&str]| sizes.iter().map(|s| s.to_string()).collect();
+
+        Self {
+            secure_base_url: String::from("https://image.tmdb.org/t/p/"),
+            backdrop_sizes: sizes(&["w300", "w780", "w1280", "original"]),
+            logo_sizes: sizes(&["w45", "w92", "w154", "w185", "w300", "w500", "original"]),
+            poster_sizes: sizes(&["w92", "w154", "w185", "w342", "w500", "w780", "original"]),
+        }
     }
 }
 
-/* ======================================================================================================================== */
-/* ====================================================== UNIT TESTS ====================================================== */
-/* ======================================================================================================================== */
+#[derive(Debug, Deserialize, Clone)]
+pub struct ConfigurationResponse {
+    pub images: ImageConfiguration,
+}
 
-#[allow(dead_code)]
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use httpmock::prelude::*;
+impl ImageConfiguration {
+    fn sizes(&self, kind: ImageKind) -> &[String] {
+        match kind {
+            ImageKind::Poster => &self.poster_sizes,
+            ImageKind::Backdrop => &self.backdrop_sizes,
+            ImageKind::Logo => &self.logo_sizes,
+        }
+    }
 
-    lazy_static! {
-        static ref MOCK_TMDB_VALID: MockServer = MockServer::start();
-        static ref MOCK_TMDB_INVALID: MockServer = MockServer::start();
+    /* Smallest size at least width pixels wide - original if none are that wide or no width is asked for */
+    pub fn size_for(&self, kind: ImageKind, width: Option<u32>) -> &str {
+        let width = match width {
+            Some(width) => width,
+            None => return "original",
+        };
+
+        self.sizes(kind)
+            .iter()
+            .filter_map(|size| {
+                size.strip_prefix('w')
+                    .and_then(|w| w.parse::<u32>().ok())
+                    .map(|w| (w, size.as_str()))
+            })
+            .filter(|(w, _)| *w >= width)
+            .min_by_key(|(w, _)| *w)
+            .map(|(_, size)| size)
+            .unwrap_or("original")
+    }
+
+    /* path is the fragment TMDB returns, e.g. /fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg */
+    pub fn url(&self, kind: ImageKind, path: &str, width: Option<u32>) -> String {
+        format!(
+            "{}/{}/{}",
+            self.secure_base_url.trim_end_matches('/'),
+            self.size_for(kind, width),
+            path.trim_start_matches('/')
+        )
+    }
+
+    pub fn poster_url(&self, path: &str, width: Option<u32>) -> String {
+        self.url(ImageKind::Poster, path, width)
+    }
+
+    pub fn backdrop_url(&self, path: &str, width: Option<u32>) -> String {
+        self.url(ImageKind::Backdrop, path, width)
+    }
+
+    pub fn logo_url(&self, path: &str, width: Option<u32>) -> String {
+        self.url(ImageKind::Logo, path, width)
+    }
+}
+
+/* Fills in absolute image URLs before a response goes out - TMDB only sends path fragments */
+pub trait ResolveImages {
+    fn resolve_images(&mut self, images: &ImageConfiguration);
+}
+
+impl<T: ResolveImages> ResolveImages for Vec<T> {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.iter_mut().for_each(|item| item.resolve_images(images));
+    }
+}
+
+impl<T: ResolveImages> ResolveImages for PagedResponse<T> {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.results.resolve_images(images);
+    }
+}
+
+impl ResolveImages for Movie {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.poster_url = self
+            .poster_path
+            .as_ref()
+            .map(|path| images.poster_url(path, Some(POSTER_WIDTH)));
+    }
+}
+
+impl ResolveImages for TvShow {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.poster_url = self
+            .poster_path
+            .as_ref()
+            .map(|path| images.poster_url(path, Some(POSTER_WIDTH)));
+    }
+}
+
+impl ResolveImages for MovieDetails {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.poster_url = self
+            .poster_path
+            .as_ref()
+            .map(|path| images.poster_url(path, Some(POSTER_WIDTH)));
+        self.backdrop_url = self
+            .backdrop_path
+            .as_ref()
+            .map(|path| images.backdrop_url(path, Some(BACKDROP_WIDTH)));
+    }
+}
+
+impl ResolveImages for TvDetails {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.poster_url = self
+            .poster_path
+            .as_ref()
+            .map(|path| images.poster_url(path, Some(POSTER_WIDTH)));
+        self.backdrop_url = self
+            .backdrop_path
+            .as_ref()
+            .map(|path| images.backdrop_url(path, Some(BACKDROP_WIDTH)));
+    }
+}
+
+impl ResolveImages for WatchProvider {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.logo_url = Some(images.logo_url(&self.logo_path, Some(LOGO_WIDTH)));
+    }
+}
+
+impl ResolveImages for WatchProviderRegion {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        for providers in [
+            &mut self.flatrate,
+            &mut self.free,
+            &mut self.ads,
+            &mut self.rent,
+            &mut self.buy,
+        ] {
+            providers.resolve_images(images);
+        }
+    }
+}
+
+impl ResolveImages for MovieRecommendation {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.movie.resolve_images(images);
+        self.providers.resolve_images(images);
+        self.explanation.providers.resolve_images(images);
+    }
+}
+
+impl ResolveImages for TvRecommendation {
+    fn resolve_images(&mut self, images: &ImageConfiguration) {
+        self.show.resolve_images(images);
+        self.providers.resolve_images(images);
+    }
+}
+
+/* How the Tmdb client retries rate limited and transient failures */
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /* Total attempts including the first one - 1 disables retries */
+    pub max_attempts: u32,
+    /* Delay before the first retry, doubled for every retry after that */
+    pub base_delay: Duration,
+    /* Longest we'll wait between attempts - a Retry-After longer than this isn't waited out */
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn no_retries() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
     }
 
-    #[allow(dead_code)]
-    fn get_json_from_file(file_name: &str) -> String {
-        fs::read_to_string(format!("src/test/{}.json", file_name)).expect("Error parsing file")
+    /* Exponential backoff after the given number of attempts, jittered down by up to half */
+    /* so a burst of spawned requests doesn't retry in lockstep */
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(16);
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay);
+
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/* Settings for the HTTP client shared by every clone of a Tmdb */
+#[derive(Debug, Clone)]
+pub struct TmdbClientConfig {
+    /* How long to wait for a connection to TMDB to be established */
+    pub connect_timeout: Duration,
+    /* How long a single request may take from start to finish, including reading the body */
+    pub request_timeout: Duration,
+    /* Idle connections kept open to TMDB for reuse */
+    pub pool_max_idle_per_host: usize,
+    /* How long an idle connection is kept before it's closed */
+    pub pool_idle_timeout: Duration,
+    pub user_agent: String,
+}
+
+impl Default for TmdbClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(15),
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Duration::from_secs(90),
+            user_agent: String::from("rust web-api demo"),
+        }
+    }
+}
+
+impl TmdbClientConfig {
+    pub fn build_client(&self) -> Result<reqwest::Client, TmdbError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .user_agent(self.user_agent.as_str())
+            .build()?;
+
+        Ok(client)
+    }
+}
+
+/* Struct for interacting with TMDB API */
+/* reqwest::Client is reference counted, so clones share one connection pool */
+#[derive(Clone)]
+pub struct Tmdb {
+    base_url: String,
+    api_key: String,
+    retry_policy: RetryPolicy,
+    client: reqwest::Client,
+    cache: Option<Arc<TmdbCache>>,
+}
+
+impl Default for Tmdb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/* Methods for TMDB API endpoints */
+impl Tmdb {
+    /* Constructor for building Tmdb object */
+    pub fn new() -> Self {
+        let api_key: String = fs::read_to_string("config/api.key")
+            .expect("Unable to read API Key!")
+            .trim()
+            .to_string();
+        let base_url: String = String::from("https://api.themoviedb.org/3");
+        Self {
+            api_key,
+            base_url,
+            retry_policy: RetryPolicy::default(),
+            client: Self::default_client(),
+            cache: None,
+        }
+    }
+
+    /* Builds a client from config that AppConfig::load has already validated, API key included */
+    pub fn from_config(config: &config::TmdbConfig) -> Result<Self, TmdbError> {
+        Self {
+            api_key: config.api_key.clone().unwrap_or_default(),
+            base_url: config.base_url.clone(),
+            retry_policy: RetryPolicy::default(),
+            client: Self::default_client(),
+            cache: None,
+        }
+        .with_client_config(&config.client_config())
+    }
+
+    /* Mocks don't retry unless asked to, so hit counts in tests stay exact */
+    pub fn mock(api_key: String, base_url: String) -> Self {
+        Self {
+            api_key,
+            base_url,
+            retry_policy: RetryPolicy::no_retries(),
+            client: Self::default_client(),
+            cache: None,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /* Swaps in a client built from the given config - fails if reqwest can't build it */
+    pub fn with_client_config(
+        mut self,
+        client_config: &TmdbClientConfig,
+    ) -> Result<Self, TmdbError> {
+        self.client = client_config.build_client()?;
+        Ok(self)
+    }
+
+    /* Caches catalog, keyword and per-movie provider lookups - shared by every clone */
+    pub fn with_cache(mut self, cache: TmdbCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    fn default_client() -> reqwest::Client {
+        // Same failure mode as reqwest::Client::new(), which only fails if the TLS backend can't initialize
+        TmdbClientConfig::default()
+            .build_client()
+            .expect("Unable to build HTTP client!")
+    }
+
+    pub fn mock_shared_instance(api_key: String, base_url: String) -> Arc<Self> {
+        Arc::new(Self::mock(api_key, base_url))
+    }
+
+    /* For building shared instance */
+    pub fn shared_instance() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    /* Private function to make TMDB API call, retrying transient failures per the retry policy */
+    /* Only ever used for GETs, so every request is safe to repeat */
+    async fn make_tmdb_request(&self, url: &String) -> Result<Response, TmdbError> {
+        let mut attempts: u32 = 1;
+
+        loop {
+            let err = match self.send_tmdb_request(url).await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+
+            if attempts >= self.retry_policy.max_attempts || !err.is_retryable() {
+                return Err(err);
+            }
+
+            let delay = match &err {
+                TmdbError::RateLimited {
+                    retry_after: Some(seconds),
+                } => {
+                    let retry_after = Duration::from_secs(*seconds);
+                    // Not worth holding the caller's request open that long
+                    if retry_after > self.retry_policy.max_delay {
+                        return Err(err);
+                    }
+                    retry_after
+                }
+                _ => self.retry_policy.backoff(attempts),
+            };
+
+            println!(
+                "{} - retrying {} in {}ms ({}/{})",
+                err,
+                url,
+                delay.as_millis(),
+                attempts + 1,
+                self.retry_policy.max_attempts
+            );
+
+            tokio::time::sleep(delay).await;
+
+            attempts += 1;
+        }
+    }
+
+    /* Private function to make a single TMDB API call - non-success statuses are turned into a TmdbError */
+    async fn send_tmdb_request(&self, url: &String) -> Result<Response, TmdbError> {
+        let response = self
+            .client
+            .get(format!("{}/{}", self.base_url, url))
+            .header(AUTHORIZATION, format!("Bearer {0}", self.api_key))
+            .header(ACCEPT, "application/json")
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| Self::parse_retry_after(value, Utc::now()));
+
+            return Err(TmdbError::RateLimited { retry_after });
+        }
+
+        // TMDB describes most failures in the body, but it isn't guaranteed to be there
+        let status_message = response
+            .json::<TmdbStatusResponse>()
+            .await
+            .ok()
+            .and_then(|body| body.status_message);
+
+        match status {
+            StatusCode::NOT_FOUND => Err(TmdbError::NotFound { status_message }),
+            _ => Err(TmdbError::Http {
+                status: status.as_u16(),
+                status_message,
+            }),
+        }
+    }
+
+    /* Private function to make TMDB API call and parse the JSON body into T */
+    async fn get_tmdb_json<T: DeserializeOwned>(&self, url: &String) -> Result<T, TmdbError> {
+        let response = self.make_tmdb_request(url).await?;
+
+        let body = response.text().await?;
+
+        Self::parse_tmdb_json(&body)
+    }
+
+    /* Same as get_tmdb_json, but answers from the cache when it can and caches what it fetches */
+    async fn get_cached_tmdb_json<T: DeserializeOwned>(
+        &self,
+        url: &String,
+        kind: CacheKind,
+    ) -> Result<T, TmdbError> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.get_tmdb_json(url).await,
+        };
+
+        if let Some(body) = cache.get(kind, url).await {
+            // Anything that doesn't parse anymore is treated as a miss and replaced below
+            if let Ok(parsed) = Self::parse_tmdb_json(&body) {
+                return Ok(parsed);
+            }
+        }
+
+        let response = self.make_tmdb_request(url).await?;
+
+        let body = response.text().await?;
+
+        let parsed = Self::parse_tmdb_json(&body)?;
+
+        cache.insert(kind, url, body).await;
+
+        Ok(parsed)
+    }
+
+    /* Retry-After is either a number of seconds or an HTTP date - a date that has already passed means retry now */
+    fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<u64> {
+        let value = value.trim();
+
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(seconds);
+        }
+
+        let retry_at = DateTime::parse_from_rfc2822(value).ok()?;
+
+        Some((retry_at.with_timezone(&Utc) - now).num_seconds().max(0) as u64)
+    }
+
+    fn parse_tmdb_json<T: DeserializeOwned>(body: &str) -> Result<T, TmdbError> {
+        serde_json::from_str::<T>(body).map_err(|error| TmdbError::Decode {
+            error,
+            body_snippet: body.chars().take(BODY_SNIPPET_LENGTH).collect(),
+        })
+    }
+
+    fn person_ids(people: &[Person]) -> String {
+        people
+            .iter()
+            .map(|p| p.id.to_string())
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+
+    /* Builds a /discover URL - criteria that haven't been set aren't filtered on */
+    /* Movies and TV share most filters, but dates, runtimes and people are named or supported differently */
+    fn discover_url(criteria: &RecommendationCriteria, page: u32, media_type: MediaType) -> String {
+        let mut url = format!(
+            "discover/{}?include_adult=false&include_video=false&language={}&page={}&sort_by={}&watch_region={}&with_watch_monetization_types={}",
+            media_type.as_str(),
+            criteria.language(),
+            page,
+            criteria.sort().discover_param(media_type),
+            criteria.region(),
+            criteria
+                .monetization_types()
+                .iter()
+                .map(|m| m.as_str())
+                .collect::<Vec<_>>()
+                .join("|")
+        );
+
+        if let Some(genres) = &criteria.genres {
+            let genre_ids: String = genres
+                .iter()
+                .map(|g| g.id.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            url.push_str(&format!("&with_genres={}", genre_ids));
+        }
+
+        if let Some(watch_providers) = &criteria.watch_providers {
+            let provider_ids: String = watch_providers
+                .iter()
+                .map(|p| p.provider_id.to_string())
+                .collect::<Vec<_>>()
+                .join("|");
+
+            url.push_str(&format!("&with_watch_providers={}", provider_ids));
+        }
+
+        // People are OR'd together - movies with every picked person in them are few and far between
+        // TV discover has no cast or crew filters
+        if media_type == MediaType::Movie {
+            if let Some(cast) = &criteria.with_cast {
+                url.push_str(&format!("&with_cast={}", Self::person_ids(cast)));
+            }
+
+            if let Some(crew) = &criteria.with_crew {
+                url.push_str(&format!("&with_crew={}", Self::person_ids(crew)));
+            }
+        }
+
+        if let Some(runtime) = &criteria.runtime {
+            let (min_runtime, max_runtime) = match media_type {
+                MediaType::Movie => runtime.runtime(),
+                MediaType::Tv => runtime.episode_runtime(),
+            };
+
+            url.push_str(&format!(
+                "&with_runtime.gte={}&with_runtime.lte={}",
+                min_runtime, max_runtime
+            ));
+        }
+
+        let vote_bounds = criteria.vote_bounds();
+
+        if let Some(min) = vote_bounds.min_vote_average {
+            url.push_str(&format!("&vote_average.gte={}", min));
+        }
+
+        if let Some(max) = vote_bounds.max_vote_average {
+            url.push_str(&format!("&vote_average.lte={}", max));
+        }
+
+        if let Some(min) = vote_bounds.min_vote_count {
+            url.push_str(&format!("&vote_count.gte={}", min));
+        }
+
+        if let Some(max) = vote_bounds.max_vote_count {
+            url.push_str(&format!("&vote_count.lte={}", max));
+        }
+
+        if let Some(decade) = &criteria.decade {
+            let (start_date, end_date) = decade.year_range();
+            let date_field = match media_type {
+                MediaType::Movie => "primary_release_date",
+                MediaType::Tv => "first_air_date",
+            };
+
+            url.push_str(&format!(
+                "&{}.gte={}-01-01&{}.lte={}-12-31",
+                date_field, start_date, date_field, end_date
+            ));
+        }
+
+        if let Some(feedback) = &criteria.feedback {
+            // TMDB can't mix AND with OR in one filter, so explicit must-haves replace voted likes
+            match (&feedback.must_have, &feedback.like) {
+                (Some(keywords), _) => url.push_str(&format!(
+                    "&with_keywords={}",
+                    keywords
+                        .iter()
+                        .map(|k| k.id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                )),
+                (None, Some(keywords)) => url.push_str(&format!(
+                    "&with_keywords={}",
+                    keywords
+                        .iter()
+                        .map(|k| k.to_string())
+                        .collect::<Vec<_>>()
+                        .join("|")
+                )),
+                (None, None) => {}
+            };
+
+            let mut avoided: Vec<i64> = feedback.dislike.clone().unwrap_or_default();
+            for keyword in feedback.avoid.iter().flatten() {
+                if !avoided.contains(&keyword.id) {
+                    avoided.push(keyword.id);
+                }
+            }
+
+            if !avoided.is_empty() {
+                url.push_str(&format!(
+                    "&without_keywords={}",
+                    avoided
+                        .iter()
+                        .map(|k| k.to_string())
+                        .collect::<Vec<_>>()
+                        .join("|")
+                ));
+            }
+        }
+
+        url
+    }
+}
+
+/* TMDB API endpoints */
+#[async_trait]
+impl MovieCatalog for Tmdb {
+    /* Searches for movie by title - helpful for retrieving movie IDs */
+    async fn search_by_title(
+        &self,
+        movie_title: &str,
+        year: Option<i32>,
+        page: u32,
+        language: &str,
+    ) -> Result<SearchByTitleResponse, TmdbError> {
+        let mut url = format!(
+            "search/movie?query={}&include_adult=false&language={}&page={}",
+            urlencoding::encode(movie_title),
+            language,
+            page.max(1)
+        );
+
+        if let Some(year) = year {
+            url.push_str(&format!("&primary_release_year={}", year));
+        }
+
+        self.get_tmdb_json::<SearchByTitleResponse>(&url).await
+    }
+
+    async fn get_keywords_for_id(&self, movie_id: &i64) -> Result<KeywordResponse, TmdbError> {
+        let url = format!("movie/{}/keywords", movie_id);
+
+        self.get_cached_tmdb_json::<KeywordResponse>(&url, CacheKind::Keywords)
+            .await
+    }
+
+    async fn search_keywords(
+        &self,
+        query: &str,
+        page: u32,
+    ) -> Result<SearchKeywordsResponse, TmdbError> {
+        let url = format!(
+            "search/keyword?query={}&page={}",
+            urlencoding::encode(query),
+            page.max(1)
+        );
+
+        self.get_tmdb_json::<SearchKeywordsResponse>(&url).await
+    }
+
+    async fn get_movie_details(
+        &self,
+        movie_id: &i64,
+        language: &str,
+    ) -> Result<MovieDetails, TmdbError> {
+        let url = format!("movie/{}?language={}", movie_id, language);
+
+        self.get_tmdb_json::<MovieDetails>(&url).await
+    }
+
+    async fn get_recommendations_for_movie(
+        &self,
+        movie_id: &i64,
+        page: u32,
+        language: &str,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        let url = format!(
+            "movie/{}/recommendations?language={}&page={}",
+            movie_id, language, page
+        );
+
+        self.get_tmdb_json::<GetRecommendationsResponse>(&url).await
+    }
+
+    async fn get_similar_movies(
+        &self,
+        movie_id: &i64,
+        page: u32,
+        language: &str,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        let url = format!(
+            "movie/{}/similar?language={}&page={}",
+            movie_id, language, page
+        );
+
+        self.get_tmdb_json::<GetRecommendationsResponse>(&url).await
+    }
+
+    async fn get_videos_for_id(
+        &self,
+        movie_id: &i64,
+        language: &str,
+    ) -> Result<VideosResponse, TmdbError> {
+        // Ask for English and untagged videos too, most movies only have trailers in a few languages
+        let video_language = language.split('-').next().unwrap_or(language);
+        let url = format!(
+            "movie/{}/videos?language={}&include_video_language={},en,null",
+            movie_id, language, video_language
+        );
+
+        self.get_tmdb_json::<VideosResponse>(&url).await
+    }
+
+    async fn get_credits_for_id(
+        &self,
+        movie_id: &i64,
+        language: &str,
+    ) -> Result<CreditsResponse, TmdbError> {
+        let url = format!("movie/{}/credits?language={}", movie_id, language);
+
+        self.get_tmdb_json::<CreditsResponse>(&url).await
+    }
+
+    async fn search_people(
+        &self,
+        name: &str,
+        language: &str,
+    ) -> Result<SearchPeopleResponse, TmdbError> {
+        let url = format!(
+            "search/person?query={}&include_adult=false&language={}&page=1",
+            urlencoding::encode(name),
+            language
+        );
+
+        self.get_tmdb_json::<SearchPeopleResponse>(&url).await
+    }
+
+    /* Gets watch providers by movie ID */
+    /* Watch providers are given by country, and by type: */
+    /* For this application we are mostly interested in "flatrate" */
+    async fn get_watch_providers_by_id(
+        &self,
+        movie_id: &str,
+    ) -> Result<GetWatchProvidersResponse, TmdbError> {
+        let url = format!("movie/{}/watch/providers", movie_id);
+
+        // TODO: Improve error handling for things not available on streaming services
+        self.get_cached_tmdb_json::<GetWatchProvidersResponse>(&url, CacheKind::MovieProviders)
+            .await
+    }
+
+    async fn get_genre_list(&self, language: &str) -> Result<GetGenresResponse, TmdbError> {
+        let url = format!("genre/movie/list?language={}", language);
+
+        self.get_cached_tmdb_json::<GetGenresResponse>(&url, CacheKind::Genres)
+            .await
+    }
+
+    async fn get_image_configuration(&self) -> Result<ImageConfiguration, TmdbError> {
+        let url = String::from("configuration");
+
+        let response = self
+            .get_cached_tmdb_json::<ConfigurationResponse>(&url, CacheKind::Configuration)
+            .await?;
+
+        Ok(response.images)
+    }
+
+    async fn get_providers_list(
+        &self,
+        region: &str,
+        language: &str,
+    ) -> Result<GetProvidersResponse, TmdbError> {
+        let url = format!(
+            "watch/providers/movie?language={}&watch_region={}",
+            language, region
+        );
+
+        self.get_cached_tmdb_json::<GetProvidersResponse>(&url, CacheKind::ProviderList)
+            .await
+    }
+
+    /* Fetches a single page of movie /discover results */
+    async fn get_recommendations(
+        &self,
+        criteria: &RecommendationCriteria,
+        page: u32,
+    ) -> Result<GetRecommendationsResponse, TmdbError> {
+        let url = Self::discover_url(criteria, page, MediaType::Movie);
+
+        self.get_tmdb_json::<GetRecommendationsResponse>(&url).await
+    }
+
+    async fn get_tv_recommendations(
+        &self,
+        criteria: &RecommendationCriteria,
+        page: u32,
+    ) -> Result<GetTvRecommendationsResponse, TmdbError> {
+        let url = Self::discover_url(criteria, page, MediaType::Tv);
+
+        self.get_tmdb_json::<GetTvRecommendationsResponse>(&url)
+            .await
+    }
+
+    async fn get_tv_details(&self, show_id: &i64, language: &str) -> Result<TvDetails, TmdbError> {
+        let url = format!("tv/{}?language={}", show_id, language);
+
+        self.get_tmdb_json::<TvDetails>(&url).await
+    }
+
+    async fn get_tv_keywords_for_id(&self, show_id: &i64) -> Result<KeywordResponse, TmdbError> {
+        let url = format!("tv/{}/keywords", show_id);
+
+        self.get_cached_tmdb_json::<KeywordResponse>(&url, CacheKind::Keywords)
+            .await
+    }
+
+    async fn get_tv_watch_providers_by_id(
+        &self,
+        show_id: &str,
+    ) -> Result<GetWatchProvidersResponse, TmdbError> {
+        let url = format!("tv/{}/watch/providers", show_id);
+
+        self.get_cached_tmdb_json::<GetWatchProvidersResponse>(&url, CacheKind::MovieProviders)
+            .await
+    }
+
+    async fn get_tv_genre_list(&self, language: &str) -> Result<GetGenresResponse, TmdbError> {
+        let url = format!("genre/tv/list?language={}", language);
+
+        self.get_cached_tmdb_json::<GetGenresResponse>(&url, CacheKind::Genres)
+            .await
+    }
+
+    async fn get_tv_providers_list(
+        &self,
+        region: &str,
+        language: &str,
+    ) -> Result<GetProvidersResponse, TmdbError> {
+        let url = format!(
+            "watch/providers/tv?language={}&watch_region={}",
+            language, region
+        );
+
+        self.get_cached_tmdb_json::<GetProvidersResponse>(&url, CacheKind::ProviderList)
+            .await
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+}
+
+/* ======================================================================================================================== */
+/* ====================================================== UNIT TESTS ====================================================== */
+/* ======================================================================================================================== */
+
+#[allow(dead_code)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    lazy_static! {
+        static ref MOCK_TMDB_VALID: MockServer = MockServer::start();
+        static ref MOCK_TMDB_INVALID: MockServer = MockServer::start();
+    }
+
+    #[allow(dead_code)]
+    fn get_json_from_file(file_name: &str) -> String {
+        fs::read_to_string(format!("src/test/{}.json", file_name)).expect("Error parsing file")
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_keywords_invalid() {
+        let movie_id = 12345;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let keyword_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/keywords", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(500);
+        });
+
+        let response = tmdb.get_keywords_for_id(&movie_id).await;
+
+        keyword_mock.assert();
+
+        assert!(response.is_err());
+
+        response.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keywords_not_found() {
+        let movie_id = 404404;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let keyword_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/keywords", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(404).body(
+                r#"{"success":false,"status_code":34,"status_message":"The resource you requested could not be found."}"#,
+            );
+        });
+
+        let response = tmdb.get_keywords_for_id(&movie_id).await;
+
+        keyword_mock.assert();
+
+        match response {
+            Err(TmdbError::NotFound { status_message }) => assert_eq!(
+                status_message.as_deref(),
+                Some("The resource you requested could not be found.")
+            ),
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keywords_rate_limited() {
+        let movie_id = 429429;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let keyword_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/keywords", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(429).header("Retry-After", "7");
+        });
+
+        let response = tmdb.get_keywords_for_id(&movie_id).await;
+
+        keyword_mock.assert();
+
+        match response {
+            Err(TmdbError::RateLimited { retry_after }) => assert_eq!(retry_after, Some(7)),
+            other => panic!("Expected RateLimited, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keywords_unauthorized() {
+        let movie_id = 401401;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let keyword_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/keywords", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(401).body(
+                r#"{"success":false,"status_code":7,"status_message":"Invalid API key: You must be granted a valid key."}"#,
+            );
+        });
+
+        let response = tmdb.get_keywords_for_id(&movie_id).await;
+
+        keyword_mock.assert();
+
+        match response {
+            Err(TmdbError::Http {
+                status,
+                status_message,
+            }) => {
+                assert_eq!(status, 401);
+                assert!(status_message.unwrap().starts_with("Invalid API key"));
+            }
+            other => panic!("Expected Http, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keywords_decode_error() {
+        let movie_id = 500500;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let keyword_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/keywords", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200)
+                .body(r#"{"id":500500,"keywords":"not a list"}"#);
+        });
+
+        let response = tmdb.get_keywords_for_id(&movie_id).await;
+
+        keyword_mock.assert();
+
+        match response {
+            Err(TmdbError::Decode { body_snippet, .. }) => {
+                assert!(body_snippet.contains("not a list"))
+            }
+            other => panic!("Expected Decode, got {:?}", other),
+        }
+    }
+
+    fn test_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(400),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+
+    // The request runs in a spawned task, so these tests stick to httpmock's async API to avoid blocking it
+    async fn wait_for_hits(mock: &httpmock::Mock<'_>, hits: usize) {
+        while mock.hits_async().await < hits {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_rate_limited() {
+        let movie_id = 438631;
+        let api_key = String::from("supersecret");
+        let server = MockServer::start_async().await;
+
+        let tmdb =
+            Tmdb::mock(api_key.clone(), server.base_url()).with_retry_policy(test_retry_policy());
+
+        let rate_limited_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/movie/{}/keywords", movie_id))
+                    .header("Authorization", format!("Bearer {}", &api_key));
+                then.status(429).header("Retry-After", "1");
+            })
+            .await;
+
+        let request = tokio::spawn(async move { tmdb.get_keywords_for_id(&movie_id).await });
+
+        // The client sits out the Retry-After second, which leaves time to swap in a success
+        wait_for_hits(&rate_limited_mock, 1).await;
+        rate_limited_mock.delete_async().await;
+
+        let keyword_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/movie/{}/keywords", movie_id))
+                    .header("Authorization", format!("Bearer {}", &api_key));
+                then.status(200)
+                    .body(get_json_from_file("keywords_response"));
+            })
+            .await;
+
+        let response = request.await.unwrap();
+
+        keyword_mock.assert_async().await;
+
+        assert_eq!(response.unwrap().id, movie_id);
+    }
+
+    #[tokio::test]
+    async fn test_retry_service_unavailable() {
+        let movie_id = 438631;
+        let api_key = String::from("supersecret");
+        let server = MockServer::start_async().await;
+
+        let tmdb =
+            Tmdb::mock(api_key.clone(), server.base_url()).with_retry_policy(test_retry_policy());
+
+        let unavailable_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/movie/{}/keywords", movie_id))
+                    .header("Authorization", format!("Bearer {}", &api_key));
+                then.status(503);
+            })
+            .await;
+
+        let request = tokio::spawn(async move { tmdb.get_keywords_for_id(&movie_id).await });
+
+        // Second backoff is at least 400ms, plenty of time to swap in a success
+        wait_for_hits(&unavailable_mock, 2).await;
+        unavailable_mock.delete_async().await;
+
+        let keyword_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/movie/{}/keywords", movie_id))
+                    .header("Authorization", format!("Bearer {}", &api_key));
+                then.status(200)
+                    .body(get_json_from_file("keywords_response"));
+            })
+            .await;
+
+        let response = request.await.unwrap();
+
+        keyword_mock.assert_async().await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let api_key = String::from("supersecret");
+        let server = MockServer::start_async().await;
+
+        let tmdb = Tmdb::mock(api_key.clone(), server.base_url()).with_retry_policy(RetryPolicy {
+            base_delay: Duration::from_millis(10),
+            ..test_retry_policy()
+        });
+
+        let unavailable_mock = server
+            .mock_async(|when, then| {
+                when.method(GET).path("/movie/503/keywords");
+                then.status(503);
+            })
+            .await;
+
+        let not_found_mock = server
+            .mock_async(|when, then| {
+                when.method(GET).path("/movie/404/keywords");
+                then.status(404);
+            })
+            .await;
+
+        let too_long_mock = server
+            .mock_async(|when, then| {
+                when.method(GET).path("/movie/429/keywords");
+                then.status(429).header("Retry-After", "120");
+            })
+            .await;
+
+        let response = tmdb.get_keywords_for_id(&503).await;
+        unavailable_mock.assert_hits_async(3).await;
+        assert!(matches!(response, Err(TmdbError::Http { status: 503, .. })));
+
+        // Not worth retrying something that doesn't exist
+        let response = tmdb.get_keywords_for_id(&404).await;
+        not_found_mock.assert_hits_async(1).await;
+        assert!(matches!(response, Err(TmdbError::NotFound { .. })));
+
+        // Or waiting longer than the policy allows
+        let response = tmdb.get_keywords_for_id(&429).await;
+        too_long_mock.assert_hits_async(1).await;
+        assert!(matches!(
+            response,
+            Err(TmdbError::RateLimited {
+                retry_after: Some(120)
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let api_key = String::from("supersecret");
+        let server = MockServer::start_async().await;
+
+        let client_config = TmdbClientConfig {
+            request_timeout: Duration::from_millis(200),
+            ..TmdbClientConfig::default()
+        };
+
+        let tmdb = Tmdb::mock(api_key.clone(), server.base_url())
+            .with_client_config(&client_config)
+            .unwrap();
+
+        let slow_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/movie/438631/keywords")
+                    .header("User-Agent", "rust web-api demo");
+                then.status(200)
+                    .delay(Duration::from_secs(2))
+                    .body(get_json_from_file("keywords_response"));
+            })
+            .await;
+
+        let response = tmdb.get_keywords_for_id(&438631).await;
+
+        slow_mock.assert_async().await;
+
+        match response {
+            Err(TmdbError::Transport(err)) => assert!(err.is_timeout()),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:27:30 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(Tmdb::parse_retry_after("7", now), Some(7));
+        assert_eq!(Tmdb::parse_retry_after(" 120 ", now), Some(120));
+        assert_eq!(
+            Tmdb::parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(30)
+        );
+        // A date in the past doesn't need waiting out
+        assert_eq!(
+            Tmdb::parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(0)
+        );
+        assert_eq!(Tmdb::parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = test_retry_policy();
+
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(200) && first <= Duration::from_millis(400));
+
+            let second = policy.backoff(2);
+            assert!(second >= Duration::from_millis(400) && second <= Duration::from_millis(800));
+
+            // Capped at max_delay no matter how many attempts have been made
+            assert!(policy.backoff(30) <= policy.max_delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keywords() {
+        let movie_id = 438631;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let keywords_response = get_json_from_file("keywords_response");
+
+        let keyword_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/keywords", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(keywords_response);
+        });
+
+        let response = tmdb.get_keywords_for_id(&movie_id).await;
+
+        keyword_mock.assert();
+
+        assert!(response.is_ok());
+
+        let response = response.unwrap();
+
+        assert_eq!(response.id, movie_id);
+
+        assert!(!response.keywords.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keywords_cached() {
+        let movie_id = 438631;
+        let api_key = String::from("supersecret");
+        let server = MockServer::start_async().await;
+
+        let tmdb = Tmdb::mock(api_key.clone(), server.base_url())
+            .with_cache(TmdbCache::new(10, CacheTtls::default()));
+
+        let keyword_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path(format!("/movie/{}/keywords", movie_id))
+                    .header("Authorization", format!("Bearer {}", &api_key));
+                then.status(200)
+                    .body(get_json_from_file("keywords_response"));
+            })
+            .await;
+
+        let first = tmdb.get_keywords_for_id(&movie_id).await.unwrap();
+        let second = tmdb.clone().get_keywords_for_id(&movie_id).await.unwrap();
+
+        // Clones share the cache, so TMDB is only asked once
+        keyword_mock.assert_hits_async(1).await;
+
+        assert_eq!(first, second);
+
+        let stats = tmdb.cache_stats().unwrap();
+        let keyword_stats = stats
+            .kinds
+            .iter()
+            .find(|k| k.kind == CacheKind::Keywords)
+            .unwrap();
+
+        assert_eq!((keyword_stats.hits, keyword_stats.misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_movie_details() {
+        let movie_id = 335984;
+        let api_key = String::from("supersecret");
+
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let details_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}", movie_id))
+                .query_param("language", "en-US")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200)
+                .body(get_json_from_file("movie_details_response"));
+        });
+
+        let details = tmdb
+            .get_movie_details(&movie_id, DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+
+        details_mock.assert();
+
+        assert_eq!(details.title, "Blade Runner 2049");
+        assert_eq!(details.runtime, Some(164));
+        assert_eq!(
+            details.tagline.as_deref(),
+            Some("The key to the future is finally unearthed.")
+        );
+        assert_eq!(details.imdb_id.as_deref(), Some("tt1856101"));
+        assert_eq!(details.budget, 150000000);
+        assert_eq!(details.vote_count, 13122);
+        assert_eq!(
+            details.genres,
+            vec![
+                Genre {
+                    id: 878,
+                    name: String::from("Science Fiction")
+                },
+                Genre {
+                    id: 18,
+                    name: String::from("Drama")
+                }
+            ]
+        );
+        assert_eq!(
+            details
+                .spoken_languages
+                .iter()
+                .map(|l| l.iso_639_1.as_str())
+                .collect::<Vec<_>>(),
+            vec!["en", "fi"]
+        );
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_watch_providers_invalid() {
+        let movie_id = String::from("456");
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let provider_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/watch/providers", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(500);
+        });
+
+        let response = tmdb.get_watch_providers_by_id(&movie_id).await;
+
+        provider_mock.assert();
+
+        assert!(response.is_err());
+
+        response.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_providers() {
+        let movie_id = String::from("123");
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let watch_provider_response = get_json_from_file("watch_provider_response");
+
+        let provider_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/watch/providers", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(watch_provider_response);
+        });
+
+        let watch_provider = WatchProvider {
+            logo_path: "/bxBlRPEPpMVDc4jMhSrTf2339DW.jpg".to_string(),
+            logo_url: None,
+            provider_id: 15,
+            provider_name: "Hulu".to_string(),
+        };
+
+        let response = tmdb.get_watch_providers_by_id(&movie_id).await;
+
+        provider_mock.assert();
+
+        assert!(response.is_ok());
+
+        let response = response.unwrap();
+
+        assert_eq!(response.flatrate_for_region("US")[0], watch_provider);
+
+        // Regions with only buy/rent options have no subscription providers
+        assert!(response.flatrate_for_region("CA").is_empty());
+
+        let magenta_tv = WatchProvider {
+            logo_path: "/lrEigPPAhggq02q53uM8vdWAIUX.jpg".to_string(),
+            logo_url: None,
+            provider_id: 178,
+            provider_name: "MagentaTV".to_string(),
+        };
+
+        assert_eq!(response.flatrate_for_region("DE"), vec![magenta_tv]);
+
+        assert!(response.flatrate_for_region("XX").is_empty());
+
+        // Rent and buy are only kept when asked for
+        let flatrate_only = response.providers_for_region("US", &[MonetizationType::Flatrate]);
+
+        assert_eq!(flatrate_only.flatrate.len(), 1);
+        assert!(flatrate_only.rent.is_empty());
+        assert!(flatrate_only.buy.is_empty());
+
+        let with_rent = response
+            .providers_for_region("US", &[MonetizationType::Flatrate, MonetizationType::Rent]);
+
+        assert_eq!(with_rent.flatrate.len(), 1);
+        assert!(with_rent.rent.iter().any(|p| p.provider_name == "Vudu"));
+        assert!(with_rent.buy.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_region() {
+        assert_eq!(normalize_region("ca"), Some("CA".to_string()));
+        assert_eq!(normalize_region(" GB "), Some("GB".to_string()));
+        assert_eq!(normalize_region("USA"), None);
+        assert_eq!(normalize_region("1A"), None);
+        assert_eq!(normalize_region(""), None);
+    }
+
+    #[test]
+    fn test_normalize_language() {
+        assert_eq!(normalize_language("fr"), Some("fr".to_string()));
+        assert_eq!(normalize_language("FR_ca"), Some("fr-CA".to_string()));
+        assert_eq!(normalize_language(" pt-br "), Some("pt-BR".to_string()));
+        assert_eq!(normalize_language("english"), None);
+        assert_eq!(normalize_language("en-US-x"), None);
+        assert_eq!(normalize_language(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_recommendations_localized() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let discover_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/discover/movie")
+                .query_param("language", "fr-CA")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(discover_page_body(1, 1, &[1, 2]));
+        });
+
+        let criteria = RecommendationCriteria {
+            language: Some("fr-CA".to_string()),
+            ..RecommendationCriteria::default()
+        };
+
+        let response = tmdb.get_recommendations(&criteria, 1).await.unwrap();
+
+        discover_mock.assert();
+
+        assert_eq!(response.results.len(), 2);
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_genres_invalid() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let genre_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path("/genre/movie/list")
+                .query_param("language", "en")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(500);
+        });
+
+        genre_mock.assert();
+
+        let response = tmdb.get_genre_list("en").await;
+
+        assert!(response.is_err());
+
+        response.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_genres() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let genre_response = get_json_from_file("genres_response");
+
+        let genre_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/genre/movie/list")
+                .query_param("language", "en")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(genre_response);
+        });
+
+        let test_genre = Genre {
+            id: 28,
+            name: "Action".to_string(),
+        };
+
+        let response = tmdb.get_genre_list("en").await;
+
+        genre_mock.assert();
+
+        assert!(response.is_ok());
+
+        let response = response.unwrap();
+
+        assert!(!response.genres.is_empty());
+
+        assert!(response.genres.iter().any(|g| g == &test_genre));
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_provider_list_invalid() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_INVALID.base_url());
+
+        let provider_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+            when.method(GET)
+                .path("/watch/providers/movie")
+                .query_param("language", "en-US")
+                .query_param("watch_region", "US")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(500);
+        });
+
+        provider_mock.assert();
+
+        let response = tmdb.get_providers_list("US", DEFAULT_LANGUAGE).await;
+
+        assert!(response.is_err());
+
+        response.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_providers_list() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let providers_response = get_json_from_file("watch_providers_list_response");
+
+        let provider_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/watch/providers/movie")
+                .query_param("language", "en-US")
+                .query_param("watch_region", "US")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(providers_response);
+        });
+
+        let test_provider = WatchProvider {
+            logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
+            logo_url: None,
+            provider_name: "Netflix".to_string(),
+            provider_id: 8,
+        };
+
+        let response = tmdb.get_providers_list("US", DEFAULT_LANGUAGE).await;
+
+        provider_mock.assert();
+
+        assert!(response.is_ok());
+
+        let response = response.unwrap();
+
+        assert!(!response.results.is_empty());
+
+        assert!(response.results.iter().any(|p| p == &test_provider));
+    }
+
+    #[tokio::test]
+    async fn test_get_recommendations() {
+        let genres = vec![
+            Genre {
+                id: 28,
+                name: "Action".to_string(),
+            },
+            Genre {
+                id: 12,
+                name: "Adventure".to_string(),
+            },
+        ];
+        let watch_providers = vec![
+            WatchProvider {
+                logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
+                logo_url: None,
+                provider_name: "Netflix".to_string(),
+                provider_id: 8,
+            },
+            WatchProvider {
+                logo_path: "/7YPdUs60C9qQQQfOFCgxpnF07D9.jpg".to_string(),
+                logo_url: None,
+                provider_name: "Disney Plus".to_string(),
+                provider_id: 337,
+            },
+        ];
+
+        let runtime = Runtime::Average;
+
+        let decade = Decade::TwentyTens;
+
+        let feedback = Feedback {
+            like: None,
+            dislike: None,
+            must_have: None,
+            avoid: None,
+            keyword_profile: None,
+        };
+
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let rec_response = get_json_from_file("recommendations_response");
+
+        let rec_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/discover/movie")
+                .query_param("include_adult", "false")
+                .query_param("include_video", "false")
+                .query_param("language", "en-US")
+                .query_param("page", "1")
+                .query_param("primary_release_date.gte", "2010-01-01")
+                .query_param("primary_release_date.lte", "2019-12-31")
+                .query_param("with_runtime.gte", "90")
+                .query_param("with_runtime.lte", "120")
+                .query_param("sort_by", "popularity.desc")
+                .query_param("watch_region", "US")
+                .query_param("with_genres", "28,12")
+                .query_param("with_watch_monetization_types", "flatrate")
+                .query_param("with_watch_providers", "8|337")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(rec_response);
+        });
+
+        let movie = Movie{
+            id: 293660,
+            overview: "The origin story of former Special Forces operative turned mercenary Wade Wilson, who, after being subjected to a rogue experiment that leaves him with accelerated healing powers, adopts the alter ego Deadpool. Armed with his new abilities and a dark, twisted sense of humor, Deadpool hunts down the man who nearly destroyed his life.".to_string(),
+            popularity: 137.437,
+            poster_path: Some("/fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg".to_string()),
+            poster_url: None,
+            release_date: "2016-02-09".to_string(),
+            title: "Deadpool".to_string(),
+            vote_average: 7.61,
+            vote_count: 29603,
+            genre_ids: vec![28, 12, 35],
+        };
+
+        let criteria = RecommendationCriteria {
+            genres: Some(genres),
+            watch_providers: Some(watch_providers),
+            runtime: Some(runtime),
+            decade: Some(decade),
+            feedback: Some(feedback),
+            region: None,
+            monetization_types: None,
+            with_cast: None,
+            with_crew: None,
+            media_type: None,
+            language: None,
+            quality: None,
+            vote_bounds: None,
+            sort: None,
+            seen: None,
+            diversity: None,
+        };
+
+        let response = tmdb.get_recommendations(&criteria, 1).await;
+
+        rec_mock.assert();
+
+        assert!(response.is_ok());
+
+        let response = response.unwrap();
+
+        assert!(!response.results.is_empty());
+
+        assert!(response.results.iter().any(|m| m == &movie));
+
+        assert_eq!(response.page, 1);
+        assert_eq!(response.total_pages, 4);
+        assert_eq!(response.total_results, 63);
+    }
+
+    fn discover_page_body(page: u32, total_pages: u32, ids: &[i64]) -> String {
+        let results: Vec<serde_json::Value> = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "overview": "",
+                    "poster_path": null,
+                    "release_date": "2015-01-01",
+                    "title": format!("Movie {}", id),
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "page": page,
+            "results": results,
+            "total_pages": total_pages,
+            "total_results": ids.len() as u32 * total_pages,
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_get_recommendation_pages() {
+        let genres = vec![Genre {
+            id: 18,
+            name: "Drama".to_string(),
+        }];
+        let watch_providers = vec![WatchProvider {
+            logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
+            logo_url: None,
+            provider_name: "Netflix".to_string(),
+            provider_id: 8,
+        }];
+
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let page_mocks: Vec<_> = [(1, vec![1, 2, 3]), (2, vec![3, 4, 5]), (3, vec![6, 7])]
+            .iter()
+            .map(|(page, ids)| {
+                let body = discover_page_body(*page, 3, ids);
+                MOCK_TMDB_VALID.mock(|when, then| {
+                    when.method(GET)
+                        .path("/discover/movie")
+                        .query_param("with_genres", "18")
+                        .query_param("watch_region", "CA")
+                        .query_param("with_watch_monetization_types", "flatrate|rent")
+                        .query_param("page", page.to_string())
+                        .header("Authorization", format!("Bearer {}", &api_key));
+                    then.status(200).body(body);
+                })
+            })
+            .collect();
+
+        let criteria = RecommendationCriteria {
+            genres: Some(genres),
+            watch_providers: Some(watch_providers),
+            runtime: Some(Runtime::Average),
+            decade: Some(Decade::TwentyTens),
+            feedback: None,
+            region: Some("CA".to_string()),
+            monetization_types: Some(vec![MonetizationType::Flatrate, MonetizationType::Rent]),
+            with_cast: None,
+            with_crew: None,
+            media_type: None,
+            language: None,
+            quality: None,
+            vote_bounds: None,
+            sort: None,
+            seen: None,
+            diversity: None,
+        };
+
+        // Enough results on the first two pages, so the third is never fetched
+        let response = tmdb
+            .get_recommendation_pages(&criteria, 1, 5)
+            .await
+            .unwrap();
+
+        page_mocks[0].assert();
+        page_mocks[1].assert();
+        page_mocks[2].assert_hits(0);
+
+        assert_eq!(response.page, 2);
+        assert_eq!(response.total_pages, 3);
+        assert_eq!(
+            response.results.iter().map(|m| m.id).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+
+        // Asking for more than exists stops once total_pages is exhausted
+        let response = tmdb
+            .get_recommendation_pages(&criteria, 2, 50)
+            .await
+            .unwrap();
+
+        page_mocks[2].assert();
+
+        assert_eq!(response.page, 3);
+        assert_eq!(
+            response.results.iter().map(|m| m.id).collect::<Vec<_>>(),
+            vec![3, 4, 5, 6, 7]
+        );
+    }
+
+    fn person(id: i64, name: &str, department: &str) -> Person {
+        Person {
+            id,
+            name: name.to_string(),
+            known_for_department: Some(department.to_string()),
+            profile_path: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_people() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let people_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/search/person")
+                .query_param("query", "Denis Villeneuve")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(
+                r#"{"page":1,"results":[{"id":137427,"name":"Denis Villeneuve","known_for_department":"Directing","profile_path":"/zdDx9Xs93UIrJFWYApYR28J8M6b.jpg","popularity":12.3}],"total_pages":1,"total_results":1}"#,
+            );
+        });
+
+        let response = tmdb
+            .search_people("Denis Villeneuve", DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+
+        people_mock.assert();
+
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].id, 137427);
+        assert_eq!(
+            response.results[0].known_for_department.as_deref(),
+            Some("Directing")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_credits() {
+        let movie_id = 335984;
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let credits_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/movie/{}/credits", movie_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(
+                r#"{"id":335984,"cast":[{"id":30614,"name":"Ryan Gosling","character":"K","order":0,"profile_path":null}],"crew":[{"id":137427,"name":"Denis Villeneuve","department":"Directing","job":"Director","profile_path":null}]}"#,
+            );
+        });
+
+        let credits = tmdb
+            .get_credits_for_id(&movie_id, DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+
+        credits_mock.assert();
+
+        assert_eq!(credits.cast[0].character.as_deref(), Some("K"));
+        assert_eq!(credits.crew[0].job, "Director");
+    }
+
+    #[tokio::test]
+    async fn test_recommendations_with_people() {
+        let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
+
+        let discover_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/discover/movie")
+                .query_param("with_cast", "1373737|30614")
+                .query_param("with_crew", "137427")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(discover_page_body(1, 1, &[335984]));
+        });
+
+        let criteria = RecommendationCriteria {
+            with_cast: Some(vec![
+                person(1373737, "Florence Pugh", "Acting"),
+                person(30614, "Ryan Gosling", "Acting"),
+            ]),
+            with_crew: Some(vec![person(137427, "Denis Villeneuve", "Directing")]),
+            ..RecommendationCriteria::default()
+        };
+
+        let response = tmdb.get_recommendations(&criteria, 1).await.unwrap();
+
+        discover_mock.assert();
+
+        assert_eq!(response.results[0].id, 335984);
     }
 
     #[tokio::test]
-    #[should_panic]
-    async fn test_keywords_invalid() {
-        let movie_id = 12345;
+    async fn test_tv_recommendations() {
         let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_INVALID.base_url(),
-            api_key: api_key.clone(),
-        };
-
-        let keyword_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+        let discover_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path(format!("/movie/{}/keywords", movie_id))
+                .path("/discover/tv")
+                .query_param("with_runtime.gte", "0")
+                .query_param("with_runtime.lte", "25")
+                .query_param("first_air_date.gte", "2010-01-01")
+                .query_param("first_air_date.lte", "2019-12-31")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(500);
+            then.status(200).body(
+                r#"{"page":1,"results":[{"id":66732,"name":"Stranger Things","overview":"","poster_path":null,"first_air_date":"2016-07-15","origin_country":["US"]}],"total_pages":1,"total_results":1}"#,
+            );
         });
 
-        let response = tmdb.get_keywords_for_id(&movie_id).await;
+        let criteria = RecommendationCriteria {
+            runtime: Some(Runtime::Quick),
+            decade: Some(Decade::TwentyTens),
+            media_type: Some(MediaType::Tv),
+            // Ignored, TV discover can't filter on people
+            with_cast: Some(vec![person(30614, "Ryan Gosling", "Acting")]),
+            ..RecommendationCriteria::default()
+        };
 
-        keyword_mock.assert();
+        let response = tmdb.get_tv_recommendations(&criteria, 1).await.unwrap();
 
-        assert!(response.is_err());
+        discover_mock.assert();
 
-        response.unwrap();
+        assert_eq!(response.results[0].name, "Stranger Things");
+        assert_eq!(response.results[0].first_air_date, "2016-07-15");
     }
 
     #[tokio::test]
-    async fn test_keywords() {
-        let movie_id = 438631;
+    async fn test_tv_details_and_keywords() {
+        let show_id = 66732;
         let api_key = String::from("supersecret");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_VALID.base_url(),
-            api_key: api_key.clone(),
-        };
-
-        let keywords_response = get_json_from_file("keywords_response");
-
-        let keyword_mock = MOCK_TMDB_VALID.mock(|when, then| {
+        let details_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path(format!("/movie/{}/keywords", movie_id))
+                .path(format!("/tv/{}", show_id))
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(200).body(keywords_response);
+            then.status(200)
+                .body(get_json_from_file("tv_details_response"));
         });
 
-        let response = tmdb.get_keywords_for_id(&movie_id).await;
-
-        keyword_mock.assert();
-
-        assert!(response.is_ok());
+        let keywords_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/tv/{}/keywords", show_id))
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(
+                r#"{"id":66732,"results":[{"id":4344,"name":"small town"},{"id":9951,"name":"alien"}]}"#,
+            );
+        });
 
-        let response = response.unwrap();
+        let details = tmdb
+            .get_tv_details(&show_id, DEFAULT_LANGUAGE)
+            .await
+            .unwrap();
+        let keywords = tmdb.get_tv_keywords_for_id(&show_id).await.unwrap();
 
-        assert_eq!(response.id, movie_id);
+        details_mock.assert();
+        keywords_mock.assert();
 
-        assert!(!response.keywords.is_empty());
+        assert_eq!(details.episode_run_time, vec![50]);
+        assert_eq!(details.number_of_seasons, 4);
+        assert_eq!(details.first_air_date.as_deref(), Some("2016-07-15"));
+        assert!(details.in_production);
+        assert_eq!(keywords.keywords.len(), 2);
     }
 
     #[tokio::test]
-    #[should_panic]
-    async fn test_watch_providers_invalid() {
-        let movie_id = String::from("456");
+    async fn test_search_by_title() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_INVALID.base_url(),
-            api_key: api_key.clone(),
-        };
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let provider_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+        let search_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path(format!("/movie/{}/watch/providers", movie_id))
+                .path("/search/movie")
+                .query_param("query", "Amélie & Nino")
+                .query_param("primary_release_year", "2001")
+                .query_param("page", "2")
+                .query_param("language", "fr-FR")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(500);
+            then.status(200).body(discover_page_body(2, 3, &[194]));
         });
 
-        let response = tmdb.get_watch_providers_by_id(&movie_id).await;
+        let response = tmdb
+            .search_by_title("Amélie & Nino", Some(2001), 2, "fr-FR")
+            .await
+            .unwrap();
 
-        provider_mock.assert();
+        search_mock.assert();
 
-        assert!(response.is_err());
+        assert_eq!(response.page, 2);
+        assert_eq!(response.total_pages, 3);
+        assert_eq!(response.results[0].id, 194);
+    }
 
-        response.unwrap();
+    fn keyword(id: i64, name: &str) -> Keyword {
+        Keyword {
+            id,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_keyword_preferences() {
+        let mut feedback = Feedback::default();
+
+        feedback.add_keyword_preference(keyword(10051, "heist"), KeywordPreference::MustHave);
+        feedback.add_keyword_preference(keyword(4379, "time travel"), KeywordPreference::MustHave);
+
+        // Switching a keyword moves it rather than leaving it in both lists
+        feedback.add_keyword_preference(keyword(4379, "time travel"), KeywordPreference::Avoid);
+
+        assert_eq!(feedback.must_have, Some(vec![keyword(10051, "heist")]));
+        assert_eq!(feedback.avoid, Some(vec![keyword(4379, "time travel")]));
+
+        assert!(feedback.remove_keyword_preference(4379));
+        assert!(!feedback.remove_keyword_preference(4379));
+        assert_eq!(feedback.avoid, None);
     }
 
     #[tokio::test]
-    async fn test_watch_providers() {
-        let movie_id = String::from("123");
+    async fn test_recommendations_keyword_preferences() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_VALID.base_url(),
-            api_key: api_key.clone(),
-        };
-
-        let watch_provider_response = get_json_from_file("watch_provider_response");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let provider_mock = MOCK_TMDB_VALID.mock(|when, then| {
+        let discover_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path(format!("/movie/{}/watch/providers", movie_id))
+                .path("/discover/movie")
+                .query_param("with_keywords", "10051,9882")
+                .query_param("without_keywords", "818|4379")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(200).body(watch_provider_response);
+            then.status(200).body(discover_page_body(1, 1, &[1]));
         });
 
-        let watch_provider = WatchProvider {
-            logo_path: "/bxBlRPEPpMVDc4jMhSrTf2339DW.jpg".to_string(),
-            provider_id: 15,
-            provider_name: "Hulu".to_string(),
+        let criteria = RecommendationCriteria {
+            feedback: Some(Feedback {
+                like: Some(vec![1701]),
+                dislike: Some(vec![818]),
+                must_have: Some(vec![keyword(10051, "heist"), keyword(9882, "space")]),
+                avoid: Some(vec![
+                    keyword(4379, "time travel"),
+                    keyword(818, "based on novel"),
+                ]),
+                keyword_profile: None,
+            }),
+            ..RecommendationCriteria::default()
         };
 
-        let response = tmdb.get_watch_providers_by_id(&movie_id).await;
+        tmdb.get_recommendations(&criteria, 1).await.unwrap();
 
-        provider_mock.assert();
+        discover_mock.assert();
+    }
 
-        assert!(response.is_ok());
+    #[test]
+    fn test_vote_bounds() {
+        let criteria = RecommendationCriteria {
+            quality: Some(Quality::HiddenGems),
+            vote_bounds: Some(VoteBounds {
+                min_vote_average: Some(6.5),
+                ..VoteBounds::default()
+            }),
+            ..RecommendationCriteria::default()
+        };
+        let bounds = criteria.vote_bounds();
+
+        // Explicit bounds replace the preset's a range at a time
+        assert_eq!(
+            bounds,
+            VoteBounds {
+                min_vote_average: Some(6.5),
+                max_vote_average: None,
+                min_vote_count: Some(50),
+                max_vote_count: Some(1000),
+            }
+        );
+        assert!(bounds.contains(6.5, 50));
+        assert!(!bounds.contains(8.1, 24000));
+        assert!(!bounds.contains(5.9, 300));
+
+        // A maximum below the preset's minimum replaces the preset's range instead of contradicting it
+        let capped = RecommendationCriteria {
+            quality: Some(Quality::Acclaimed),
+            vote_bounds: Some(VoteBounds {
+                max_vote_average: Some(6.0),
+                max_vote_count: Some(100),
+                ..VoteBounds::default()
+            }),
+            sort: Some(SortBy::Rating),
+            ..RecommendationCriteria::default()
+        };
+        assert_eq!(
+            capped.vote_bounds(),
+            VoteBounds {
+                min_vote_average: None,
+                max_vote_average: Some(6.0),
+                min_vote_count: None,
+                max_vote_count: Some(100),
+            }
+        );
+        assert!(capped.vote_bounds().is_valid());
 
-        let response = response.unwrap().results.us.flatrate[0].clone();
+        assert_eq!(
+            RecommendationCriteria::default().vote_bounds(),
+            VoteBounds::default()
+        );
+        assert_eq!(Quality::Anything.vote_bounds(), VoteBounds::default());
 
-        assert_eq!(response, watch_provider);
+        assert!(Quality::Acclaimed.vote_bounds().is_valid());
+        assert!(!VoteBounds {
+            min_vote_average: Some(11.0),
+            ..VoteBounds::default()
+        }
+        .is_valid());
+        assert!(!VoteBounds {
+            min_vote_count: Some(500),
+            max_vote_count: Some(100),
+            ..VoteBounds::default()
+        }
+        .is_valid());
     }
 
     #[tokio::test]
-    #[should_panic]
-    async fn test_genres_invalid() {
+    async fn test_recommendations_quality() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_INVALID.base_url(),
-            api_key: api_key.clone(),
-        };
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let genre_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+        let discover_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path("/genre/movie/list")
-                .query_param("language", "en")
+                .path("/discover/movie")
+                .query_param("with_genres", "10752")
+                .query_param("vote_average.gte", "7.5")
+                .query_param("vote_count.gte", "1000")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(500);
+            then.status(200).body(
+                r#"{"page":1,"results":[{"id":857,"overview":"","popularity":98.2,"poster_path":null,"release_date":"1998-07-24","title":"Saving Private Ryan","vote_average":8.2,"vote_count":15904}],"total_pages":1,"total_results":1}"#,
+            );
         });
 
-        genre_mock.assert();
+        let criteria = RecommendationCriteria {
+            genres: Some(vec![Genre {
+                id: 10752,
+                name: "War".to_string(),
+            }]),
+            quality: Some(Quality::Acclaimed),
+            ..RecommendationCriteria::default()
+        };
 
-        let response = tmdb.get_genre_list().await;
+        let response = tmdb.get_recommendations(&criteria, 1).await.unwrap();
 
-        assert!(response.is_err());
+        discover_mock.assert();
 
-        response.unwrap();
+        assert_eq!(response.results[0].vote_average, 8.2);
+        assert_eq!(response.results[0].vote_count, 15904);
     }
 
     #[tokio::test]
-    async fn test_genres() {
+    async fn test_recommendations_sort() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_VALID.base_url(),
-            api_key: api_key.clone(),
-        };
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let genre_response = get_json_from_file("genres_response");
-
-        let genre_mock = MOCK_TMDB_VALID.mock(|when, then| {
+        let newest_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path("/genre/movie/list")
-                .query_param("language", "en")
+                .path("/discover/movie")
+                .query_param("sort_by", "primary_release_date.desc")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(200).body(genre_response);
+            then.status(200).body(discover_page_body(1, 1, &[1]));
+        });
+        let rated_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/discover/tv")
+                .query_param("sort_by", "vote_average.desc")
+                .query_param("vote_count.gte", RATING_SORT_MIN_VOTE_COUNT.to_string())
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200)
+                .body(r#"{"page":1,"results":[],"total_pages":1,"total_results":0}"#);
         });
 
-        let test_genre = Genre {
-            id: 28,
-            name: "Action".to_string(),
+        let newest = RecommendationCriteria {
+            sort: Some(SortBy::ReleaseDate),
+            ..RecommendationCriteria::default()
+        };
+        let rated = RecommendationCriteria {
+            sort: Some(SortBy::Rating),
+            ..RecommendationCriteria::default()
         };
 
-        let response = tmdb.get_genre_list().await;
-
-        genre_mock.assert();
-
-        assert!(response.is_ok());
+        tmdb.get_recommendations(&newest, 1).await.unwrap();
+        tmdb.get_tv_recommendations(&rated, 1).await.unwrap();
 
-        let response = response.unwrap();
+        newest_mock.assert();
+        rated_mock.assert();
 
-        assert!(!response.genres.is_empty());
+        // TV has no box office numbers and random is shuffled locally
+        assert_eq!(
+            SortBy::Revenue.discover_param(MediaType::Tv),
+            "popularity.desc"
+        );
+        assert_eq!(
+            SortBy::Random.discover_param(MediaType::Movie),
+            "popularity.desc"
+        );
 
-        assert!(response.genres.iter().any(|g| g == &test_genre));
+        // A quality preset's vote count floor wins over the rating sort's
+        let acclaimed = RecommendationCriteria {
+            quality: Some(Quality::Acclaimed),
+            ..rated
+        };
+        assert_eq!(acclaimed.vote_bounds().min_vote_count, Some(1000));
     }
 
     #[tokio::test]
-    #[should_panic]
-    async fn test_provider_list_invalid() {
+    async fn test_search_keywords() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_INVALID.base_url(),
-            api_key: api_key.clone(),
-        };
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let provider_mock = MOCK_TMDB_INVALID.mock(|when, then| {
+        let keyword_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path("/watch/providers/movie")
-                .query_param("language", "en-US")
-                .query_param("watch_region", "US")
+                .path("/search/keyword")
+                .query_param("query", "time travel")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(500);
+            then.status(200).body(
+                r#"{"page":1,"results":[{"id":4379,"name":"time travel"},{"id":193226,"name":"time travel romance"}],"total_pages":1,"total_results":2}"#,
+            );
         });
 
-        provider_mock.assert();
-
-        let response = tmdb.get_providers_list().await;
+        let response = tmdb.search_keywords("time travel", 1).await.unwrap();
 
-        assert!(response.is_err());
+        keyword_mock.assert();
 
-        response.unwrap();
+        assert_eq!(response.total_results, 2);
+        assert_eq!(response.results[0], keyword(4379, "time travel"));
     }
 
     #[tokio::test]
-    async fn test_providers_list() {
+    async fn test_related_movies() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_VALID.base_url(),
-            api_key: api_key.clone(),
-        };
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let providers_response = get_json_from_file("watch_providers_list_response");
-
-        let provider_mock = MOCK_TMDB_VALID.mock(|when, then| {
+        let recommendations_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path("/watch/providers/movie")
-                .query_param("language", "en-US")
-                .query_param("watch_region", "US")
+                .path("/movie/603/recommendations")
+                .query_param("language", "de-DE")
+                .query_param("page", "2")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(200).body(providers_response);
+            then.status(200).body(discover_page_body(2, 3, &[604, 605]));
+        });
+        let similar_mock = MOCK_TMDB_VALID.mock(|when, then| {
+            when.method(GET)
+                .path("/movie/603/similar")
+                .query_param("language", "de-DE")
+                .query_param("page", "1")
+                .header("Authorization", format!("Bearer {}", &api_key));
+            then.status(200).body(discover_page_body(1, 1, &[1858]));
         });
 
-        let test_provider = WatchProvider {
-            logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
-            provider_name: "Netflix".to_string(),
-            provider_id: 8,
-        };
+        let recommended = tmdb
+            .get_recommendations_for_movie(&603, 2, "de-DE")
+            .await
+            .unwrap();
+        let similar = tmdb.get_similar_movies(&603, 1, "de-DE").await.unwrap();
 
-        let response = tmdb.get_providers_list().await;
+        recommendations_mock.assert();
+        similar_mock.assert();
 
-        provider_mock.assert();
+        let ids = |response: &GetRecommendationsResponse| -> Vec<i64> {
+            response.results.iter().map(|m| m.id).collect()
+        };
+        assert_eq!(recommended.page, 2);
+        assert_eq!(ids(&recommended), vec![604, 605]);
+        assert_eq!(ids(&similar), vec![1858]);
+    }
 
-        assert!(response.is_ok());
+    #[tokio::test]
+    async fn test_image_configuration() {
+        let api_key = String::from("supersecret");
+        let server = MockServer::start_async().await;
+
+        let tmdb = Tmdb::mock(api_key.clone(), server.base_url())
+            .with_cache(TmdbCache::new(10, CacheTtls::default()));
+
+        let configuration_mock = server
+            .mock_async(|when, then| {
+                when.method(GET)
+                    .path("/configuration")
+                    .header("Authorization", format!("Bearer {}", &api_key));
+                then.status(200).body(
+                    r#"{"images":{"base_url":"http://image.tmdb.org/t/p/","secure_base_url":"https://images.example.org/t/p/","backdrop_sizes":["w300","w780","w1280","original"],"logo_sizes":["w45","w92","w154","w185","w300","w500","original"],"poster_sizes":["w92","w154","w185","w342","w500","w780","original"],"profile_sizes":["w45","w185","h632","original"],"still_sizes":["w92","w185","w300","original"]},"change_keys":["adult"]}"#,
+                );
+            })
+            .await;
 
-        let response = response.unwrap();
+        let images = tmdb.get_image_configuration().await.unwrap();
+        tmdb.get_image_configuration().await.unwrap();
 
-        assert!(!response.results.is_empty());
+        configuration_mock.assert_hits_async(1).await;
 
-        assert!(response.results.iter().any(|p| p == &test_provider));
-    }
+        assert_eq!(images.size_for(ImageKind::Poster, Some(300)), "w342");
+        assert_eq!(images.size_for(ImageKind::Backdrop, Some(1920)), "original");
+        assert_eq!(images.size_for(ImageKind::Logo, None), "original");
+        assert_eq!(
+            images.poster_url("/fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg", Some(500)),
+            "https://images.example.org/t/p/w500/fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg"
+        );
 
-    #[tokio::test]
-    async fn test_get_recommendations() {
-        let genres = vec![
-            Genre {
-                id: 28,
-                name: "Action".to_string(),
-            },
-            Genre {
-                id: 12,
-                name: "Adventure".to_string(),
-            },
-        ];
-        let watch_providers = vec![
-            WatchProvider {
-                logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
-                provider_name: "Netflix".to_string(),
-                provider_id: 8,
+        let mut recommendation = MovieRecommendation {
+            movie: Movie {
+                id: 293660,
+                overview: String::new(),
+                popularity: 0.0,
+                poster_path: Some("/fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg".to_string()),
+                poster_url: None,
+                release_date: "2016-02-09".to_string(),
+                title: "Deadpool".to_string(),
+                vote_average: 0.0,
+                vote_count: 0,
+                genre_ids: vec![],
             },
-            WatchProvider {
-                logo_path: "/7YPdUs60C9qQQQfOFCgxpnF07D9.jpg".to_string(),
-                provider_name: "Disney Plus".to_string(),
-                provider_id: 337,
+            providers: WatchProviderRegion {
+                flatrate: vec![WatchProvider {
+                    logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
+                    logo_url: None,
+                    provider_id: 8,
+                    provider_name: "Netflix".to_string(),
+                }],
+                ..WatchProviderRegion::default()
             },
-        ];
+            trailer: None,
+            explanation: Explanation::default(),
+        };
 
-        let runtime = Runtime::Average;
+        recommendation.resolve_images(&images);
 
-        let decade = Decade::TwentyTens;
+        assert_eq!(
+            recommendation.movie.poster_url.as_deref(),
+            Some("https://images.example.org/t/p/w342/fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg")
+        );
+        assert_eq!(
+            recommendation.providers.flatrate[0].logo_url.as_deref(),
+            Some("https://images.example.org/t/p/w92/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg")
+        );
+    }
 
-        let feedback = Feedback {
-            like: None,
-            dislike: None,
-        };
+    fn video(key: &str, video_type: &str, official: bool, language: &str, size: i32) -> Video {
+        Video {
+            key: key.to_string(),
+            name: format!("{} {}", video_type, key),
+            site: "YouTube".to_string(),
+            video_type: video_type.to_string(),
+            official,
+            size,
+            iso_639_1: language.to_string(),
+            published_at: "2016-02-01T00:00:00.000Z".to_string(),
+        }
+    }
 
+    #[tokio::test]
+    async fn test_best_trailer() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb {
-            base_url: MOCK_TMDB_VALID.base_url(),
-            api_key: api_key.clone(),
-        };
-
-        let rec_response = get_json_from_file("recommendations_response");
+        let tmdb = Tmdb::mock(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
-        let rec_mock = MOCK_TMDB_VALID.mock(|when, then| {
+        let videos_mock = MOCK_TMDB_VALID.mock(|when, then| {
             when.method(GET)
-                .path("/discover/movie")
-                .query_param("include_adult", "false")
-                .query_param("include_video", "false")
-                .query_param("language", "en-US")
-                .query_param("page", "1")
-                .query_param("primary_release_date.gte", "2010-01-01")
-                .query_param("primary_release_date.lte", "2019-12-31")
-                .query_param("with_runtime.gte", "90")
-                .query_param("with_runtime.lte", "120")
-                .query_param("sort_by", "popularity.desc")
-                .query_param("watch_region", "US")
-                .query_param("with_genres", "28,12")
-                .query_param("with_watch_monetization_types", "flatrate")
-                .query_param("with_watch_providers", "8|337")
+                .path("/movie/293660/videos")
+                .query_param("language", "fr-CA")
+                .query_param("include_video_language", "fr,en,null")
                 .header("Authorization", format!("Bearer {}", &api_key));
-            then.status(200).body(rec_response);
+            then.status(200).body(
+                r#"{"id":293660,"results":[{"iso_639_1":"en","iso_3166_1":"US","name":"Official Trailer","key":"ONHBaC-pfsk","site":"YouTube","size":1080,"type":"Trailer","official":true,"published_at":"2015-12-25T17:00:00.000Z","id":"5a2d5b1d"}]}"#,
+            );
         });
 
-        let movie = Movie{
-            id: 293660,
-            overview: "The origin story of former Special Forces operative turned mercenary Wade Wilson, who, after being subjected to a rogue experiment that leaves him with accelerated healing powers, adopts the alter ego Deadpool. Armed with his new abilities and a dark, twisted sense of humor, Deadpool hunts down the man who nearly destroyed his life.".to_string(),
-            poster_path: Some("/fSRb7vyIP8rQpL0I47P3qUsEKX3.jpg".to_string()),
-            release_date: "2016-02-09".to_string(),
-            title: "Deadpool".to_string(),
+        let videos = tmdb.get_videos_for_id(&293660, "fr-CA").await.unwrap();
+
+        videos_mock.assert();
+
+        let trailer = videos.best_trailer("fr-CA").unwrap();
+        assert_eq!(trailer.url, "https://www.youtube.com/watch?v=ONHBaC-pfsk");
+        assert_eq!(trailer.language, "en");
+
+        let mut vimeo = video("vimeo", "Trailer", true, "fr", 2160);
+        vimeo.site = "Vimeo".to_string();
+
+        let videos = VideosResponse {
+            id: 1,
+            results: vec![
+                vimeo,
+                video("clip", "Clip", true, "fr", 1080),
+                video("teaser", "Teaser", true, "fr", 1080),
+                video("fan-cut", "Trailer", false, "fr", 1080),
+                video("english", "Trailer", true, "en", 1080),
+                video("french-sd", "Trailer", true, "fr", 480),
+                video("french", "Trailer", true, "fr", 1080),
+            ],
         };
 
-        let response = tmdb
-            .get_recommendations(genres, watch_providers, runtime, decade, Some(feedback))
-            .await;
+        assert_eq!(videos.best_trailer("fr-CA").unwrap().key, "french");
+        assert_eq!(videos.best_trailer("en-US").unwrap().key, "english");
+        // Official beats an unofficial trailer even in the right language
+        assert_eq!(videos.best_trailer("de").unwrap().key, "english");
 
-        rec_mock.assert();
+        let teasers_only = VideosResponse {
+            id: 1,
+            results: vec![video("teaser", "Teaser", true, "en", 1080)],
+        };
+        assert_eq!(teasers_only.best_trailer("en").unwrap().key, "teaser");
+        assert!(VideosResponse {
+            id: 1,
+            results: vec![]
+        }
+        .best_trailer("en")
+        .is_none());
+    }
 
-        assert!(response.is_ok());
+    #[test]
+    fn test_explanation() {
+        let keyword = |id: i64, name: &str| Keyword {
+            id,
+            name: name.to_string(),
+        };
+        let provider = |provider_id: i32, provider_name: &str| WatchProvider {
+            logo_path: "/".to_string(),
+            logo_url: None,
+            provider_id,
+            provider_name: provider_name.to_string(),
+        };
 
-        let response = response.unwrap();
+        let criteria = RecommendationCriteria {
+            genres: Some(vec![
+                Genre {
+                    id: 28,
+                    name: "Action".to_string(),
+                },
+                Genre {
+                    id: 18,
+                    name: "Drama".to_string(),
+                },
+            ]),
+            watch_providers: Some(vec![provider(8, "Netflix"), provider(15, "Hulu")]),
+            runtime: Some(Runtime::MovieNight),
+            decade: Some(Decade::TwentyTens),
+            feedback: Some(Feedback {
+                must_have: Some(vec![keyword(9715, "superhero")]),
+                keyword_profile: Some(KeywordProfile {
+                    weights: HashMap::from([(10051, 2.0), (9663, -1.0)]),
+                    rounds: 1,
+                }),
+                ..Feedback::default()
+            }),
+            ..RecommendationCriteria::default()
+        };
 
-        assert!(!response.results.is_empty());
+        let movie = Movie {
+            id: 293660,
+            overview: String::new(),
+            popularity: 0.0,
+            poster_path: None,
+            poster_url: None,
+            release_date: "2016-02-09".to_string(),
+            title: "Deadpool".to_string(),
+            vote_average: 0.0,
+            vote_count: 0,
+            genre_ids: vec![28, 35],
+        };
+        let keywords = [
+            keyword(9663, "sequel"),
+            keyword(9715, "superhero"),
+            keyword(10051, "heist"),
+            keyword(180547, "marvel cinematic universe"),
+        ];
+        let providers = WatchProviderRegion {
+            flatrate: vec![provider(8, "Netflix")],
+            rent: vec![provider(2, "Apple TV"), provider(8, "Netflix")],
+            ..WatchProviderRegion::default()
+        };
 
-        assert!(response.results.iter().any(|m| m == &movie));
+        let explanation = Explanation::new(&criteria, &movie, &keywords, &providers, Some(130));
+
+        assert_eq!(
+            explanation,
+            Explanation {
+                genres: vec![Genre {
+                    id: 28,
+                    name: "Action".to_string(),
+                }],
+                // Disliked and unvoted keywords aren't reasons
+                keywords: vec![keyword(9715, "superhero"), keyword(10051, "heist")],
+                providers: vec![provider(8, "Netflix")],
+                decade: Some(Decade::TwentyTens),
+                runtime: Some(Runtime::MovieNight),
+            }
+        );
+
+        // Without the movie's details, the runtime it was discovered with isn't claimed as a reason
+        let explanation = Explanation::new(&criteria, &movie, &keywords, &providers, None);
+        assert_eq!(explanation.runtime, None);
+
+        // Nothing picked, nothing to explain
+        let explanation = Explanation::new(
+            &RecommendationCriteria::default(),
+            &movie,
+            &keywords,
+            &providers,
+            Some(130),
+        );
+        assert_eq!(explanation, Explanation::default());
     }
 }
@@ -1,49 +1,225 @@
 #![allow(dead_code, unused_variables)]
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use actix_cors::Cors;
 use actix_web::{
-    get, post,
+    delete, get,
+    http::header::{AcceptLanguage, Header, Preference, RETRY_AFTER},
+    post,
     web::{self},
-    App, HttpResponse, HttpServer, Responder,
+    App, HttpRequest, HttpResponse, HttpServer, Responder,
 };
+#[allow(unused_imports)]
 #[macro_use]
 extern crate lazy_static;
 use movie_recommendation::*;
+use serde::{Deserialize, Serialize};
 mod redis_helper;
 mod tmdb_helper;
 // TODO: Data structure for mapping general "Vibes" to genres and keywords
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    println!("starting server on port 8585");
-
     console_error_panic_hook::set_once();
 
-    let tmdb = Tmdb::new();
-    HttpServer::new(move || {
-        let cors = Cors::permissive();
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Invalid configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    redis_helper::init(&config.redis.url);
+
+    let tmdb = match Tmdb::from_config(&config.tmdb) {
+        Ok(tmdb) => tmdb,
+        Err(err) => {
+            eprintln!("Unable to build TMDB client: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let cache = build_tmdb_cache(config.limits.tmdb_cache_capacity, &config.redis.url).await;
+    let catalog: Arc<dyn MovieCatalog> = Arc::new(tmdb.with_cache(cache));
+
+    println!(
+        "starting server on {}:{}",
+        config.server.bind_address, config.server.port
+    );
 
+    let cors_config = config.cors.clone();
+    let json_payload_bytes = config.limits.json_payload_bytes;
+    let feedback_config = config.feedback.clone();
+
+    let mut server = HttpServer::new(move || {
         App::new()
-            .wrap(cors)
-            .app_data(web::Data::new(tmdb.clone()))
+            .wrap(build_cors(&cors_config))
+            .app_data(web::JsonConfig::default().limit(json_payload_bytes))
+            .app_data(web::Data::from(Arc::clone(&catalog)))
+            .app_data(web::Data::new(feedback_config.clone()))
             .service(get_runtimes)
             .service(get_decades)
+            .service(get_monetization_types)
+            .service(get_qualities)
+            .service(get_sorts)
             .service(get_simple_watch_providers)
             .service(get_movies_by_title)
+            .service(search_movies)
+            .service(get_movie_details)
+            .service(get_tv_details)
+            .service(get_movie_credits)
+            .service(get_more_like_this)
+            .service(get_people_by_name)
             .service(post_runtime)
             .service(get_genres)
             .service(start_session)
             .service(post_providers)
             .service(post_genres)
+            .service(post_cast)
+            .service(post_crew)
             .service(post_decades)
+            .service(post_region)
+            .service(post_language)
+            .service(post_monetization_types)
+            .service(post_quality)
+            .service(post_vote_bounds)
+            .service(post_sort)
+            .service(post_diversity)
+            .service(post_media_type)
             .service(get_recommendations)
             .service(post_feedback)
+            .service(search_keywords)
+            .service(post_keyword_preference)
+            .service(delete_keyword_preference)
             .service(get_session_criteria)
-    })
-    .bind("0.0.0.0:8585")?
-    .run()
-    .await
+            .service(get_cache_stats)
+    });
+
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+
+    server
+        .bind((config.server.bind_address.as_str(), config.server.port))?
+        .run()
+        .await
+}
+
+fn build_cors(cors_config: &config::CorsConfig) -> Cors {
+    if cors_config.allows_any_origin() {
+        return Cors::permissive();
+    }
+
+    cors_config
+        .allowed_origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allow_any_method()
+        .allow_any_header()
+}
+
+/* JSON body returned alongside any error status */
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: &'static str,
+    message: String,
+}
+
+impl ErrorResponse {
+    fn new(error: &'static str, message: String) -> Self {
+        Self { error, message }
+    }
+}
+
+/* Maps TMDB failures onto the status code our clients should see */
+fn tmdb_error_response(err: &TmdbError) -> HttpResponse {
+    let message = err.to_string();
+    println!("{}", &message);
+
+    match err {
+        TmdbError::NotFound { .. } => {
+            HttpResponse::NotFound().json(ErrorResponse::new("not_found", message))
+        }
+        TmdbError::RateLimited { retry_after } => {
+            let mut response = HttpResponse::TooManyRequests();
+            if let Some(seconds) = retry_after {
+                response.insert_header((RETRY_AFTER, seconds.to_string()));
+            }
+            response.json(ErrorResponse::new("rate_limited", message))
+        }
+        TmdbError::Transport(transport_err) if transport_err.is_timeout() => {
+            HttpResponse::GatewayTimeout().json(ErrorResponse::new("upstream_timeout", message))
+        }
+        TmdbError::Transport(_) => {
+            HttpResponse::BadGateway().json(ErrorResponse::new("upstream_unavailable", message))
+        }
+        TmdbError::Http { status: 401, .. } => {
+            HttpResponse::BadGateway().json(ErrorResponse::new("upstream_unauthorized", message))
+        }
+        TmdbError::Http { .. } => {
+            HttpResponse::BadGateway().json(ErrorResponse::new("upstream_error", message))
+        }
+        TmdbError::Decode { .. } => {
+            HttpResponse::BadGateway().json(ErrorResponse::new("upstream_bad_response", message))
+        }
+    }
+}
+
+fn session_error_response(err: &tmdb_helper::SessionError) -> HttpResponse {
+    match err {
+        tmdb_helper::SessionError::Tmdb(tmdb_err) => tmdb_error_response(tmdb_err),
+        tmdb_helper::SessionError::NotFound(_) => {
+            HttpResponse::NotFound().json(ErrorResponse::new("session_not_found", err.to_string()))
+        }
+        tmdb_helper::SessionError::Incomplete(_) => {
+            HttpResponse::Conflict().json(ErrorResponse::new("session_incomplete", err.to_string()))
+        }
+        tmdb_helper::SessionError::Invalid(_) => HttpResponse::InternalServerError()
+            .json(ErrorResponse::new("session_invalid", err.to_string())),
+        tmdb_helper::SessionError::Redis(_) => HttpResponse::InternalServerError()
+            .json(ErrorResponse::new("session_unavailable", err.to_string())),
+    }
+}
+
+/* Image host and sizes for absolute URLs - TMDB's usual ones if /configuration can't be fetched */
+async fn image_configuration(catalog: &dyn MovieCatalog) -> ImageConfiguration {
+    match catalog.get_image_configuration().await {
+        Ok(images) => images,
+        Err(err) => {
+            println!("Error fetching image configuration: {}", err);
+            ImageConfiguration::default()
+        }
+    }
+}
+
+/* 200 response with absolute image URLs filled in */
+async fn with_images<T: ResolveImages + Serialize>(
+    catalog: &dyn MovieCatalog,
+    mut body: T,
+) -> HttpResponse {
+    body.resolve_images(&image_configuration(catalog).await);
+
+    HttpResponse::Ok().json(body)
+}
+
+/* Cache shared by every worker, backed by the same redis instance as sessions */
+/* Redis being down at startup only costs the shared cache - the in-process one still works */
+async fn build_tmdb_cache(capacity: usize, redis_url: &str) -> TmdbCache {
+    let cache = TmdbCache::new(capacity, CacheTtls::default());
+
+    let connection = match redis::Client::open(redis_url) {
+        Ok(client) => TmdbCache::connect_redis(client).await,
+        Err(err) => Err(err),
+    };
+
+    match connection {
+        Ok(connection) => cache.with_redis(connection),
+        Err(err) => {
+            println!("Unable to use redis for the TMDB cache: {}", err);
+            cache
+        }
+    }
 }
 
 #[get("/runtimes")]
@@ -75,10 +251,92 @@ async fn get_decades() -> impl Responder {
     web::Json(decades)
 }
 
+#[get("/monetization_types")]
+async fn get_monetization_types() -> impl Responder {
+    let monetization_types = vec![
+        MonetizationType::Flatrate.info(),
+        MonetizationType::Free.info(),
+        MonetizationType::Ads.info(),
+        MonetizationType::Rent.info(),
+        MonetizationType::Buy.info(),
+    ];
+
+    web::Json(monetization_types)
+}
+
+#[get("/qualities")]
+async fn get_qualities() -> impl Responder {
+    let qualities = vec![
+        Quality::Acclaimed.info(),
+        Quality::HiddenGems.info(),
+        Quality::Anything.info(),
+    ];
+
+    web::Json(qualities)
+}
+
+#[get("/sorts")]
+async fn get_sorts() -> impl Responder {
+    let sorts = vec![
+        SortBy::Relevance.info(),
+        SortBy::Popularity.info(),
+        SortBy::Rating.info(),
+        SortBy::ReleaseDate.info(),
+        SortBy::Revenue.info(),
+        SortBy::Random.info(),
+    ];
+
+    web::Json(sorts)
+}
+
+/* Most preferred Accept-Language entry TMDB can understand, if the request sent any */
+fn accept_language(req: &HttpRequest) -> Option<String> {
+    AcceptLanguage::parse(req)
+        .ok()?
+        .ranked()
+        .into_iter()
+        .find_map(|preference| match preference {
+            Preference::Specific(tag) => normalize_language(tag.as_str()),
+            Preference::Any => None,
+        })
+}
+
+/* Language for requests that aren't tied to a session */
+fn request_language(req: &HttpRequest) -> String {
+    accept_language(req).unwrap_or_else(|| DEFAULT_LANGUAGE.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchProviderQuery {
+    region: Option<String>,
+    media_type: Option<MediaType>,
+}
+
 #[get("/simplewatchproviders")]
-async fn get_simple_watch_providers(tmdb: web::Data<Tmdb>) -> impl Responder {
-    let tmdb: Arc<Tmdb> = tmdb.into_inner();
-    let providers = tmdb.get_providers_list();
+async fn get_simple_watch_providers(
+    catalog: web::Data<dyn MovieCatalog>,
+    query: web::Query<WatchProviderQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    let region = match &query.region {
+        None => DEFAULT_REGION.to_string(),
+        Some(region) => match normalize_region(region) {
+            Some(region) => region,
+            None => {
+                return HttpResponse::BadRequest().json(ErrorResponse::new(
+                    "invalid_region",
+                    format!("{} is not an ISO 3166-1 country code", region),
+                ))
+            }
+        },
+    };
+
+    let catalog: Arc<dyn MovieCatalog> = catalog.into_inner();
+    let language = request_language(&req);
+    let providers = match query.media_type.unwrap_or_default() {
+        MediaType::Movie => catalog.get_providers_list(&region, &language),
+        MediaType::Tv => catalog.get_tv_providers_list(&region, &language),
+    };
     let supported_providers = vec![
         "Netflix",
         "Hulu",
@@ -93,19 +351,15 @@ async fn get_simple_watch_providers(tmdb: web::Data<Tmdb>) -> impl Responder {
     ];
     println!("Getting watch providers");
     match providers.await {
-        Err(err) => HttpResponse::InternalServerError().json("Error fetching providers"),
+        Err(err) => tmdb_error_response(&err),
         Ok(providers) => {
-            let mut provider_output: Vec<WatchProvider> = providers
+            let provider_output: Vec<WatchProvider> = providers
                 .results
                 .into_iter()
                 .filter(|p| supported_providers.contains(&p.provider_name.as_str()))
                 .collect();
 
-            for provider in &mut provider_output {
-                provider.logo_path = str::replace(provider.logo_path.as_str(), "jpg", "svg");
-            }
-
-            HttpResponse::Ok().json(provider_output)
+            with_images(catalog.as_ref(), provider_output).await
         }
     }
 }
@@ -113,14 +367,215 @@ async fn get_simple_watch_providers(tmdb: web::Data<Tmdb>) -> impl Responder {
 #[get("/movies/{movie_title}")]
 async fn get_movies_by_title(
     movie_title: web::Path<String>,
-    tmdb: web::Data<Tmdb>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
 ) -> impl Responder {
     println!("Got a request for {}", movie_title);
-    let movies = tmdb_helper::get_movies_from_title(movie_title.into_inner(), tmdb.into_inner())
+    match tmdb_helper::get_movies_from_title(
+        movie_title.into_inner(),
+        catalog.clone().into_inner(),
+        &request_language(&req),
+    )
+    .await
+    {
+        Ok(movies) => with_images(catalog.get_ref(), movies).await,
+        Err(err) => tmdb_error_response(&err),
+    }
+}
+
+/* TMDB won't page search results past this one */
+const MAX_SEARCH_PAGE: u32 = 500;
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    query: Option<String>,
+    year: Option<i32>,
+    page: Option<u32>,
+    language: Option<String>,
+}
+
+#[get("/search")]
+async fn search_movies(
+    query: web::Query<SearchQuery>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
+) -> impl Responder {
+    let search_query = query.into_inner();
+
+    let title = match search_query.query.as_deref().map(str::trim) {
+        Some(title) if !title.is_empty() => title.to_string(),
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "missing_query",
+                String::from("query must not be empty"),
+            ))
+        }
+    };
+
+    let page = search_query.page.unwrap_or(1);
+    if !(1..=MAX_SEARCH_PAGE).contains(&page) {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(
+            "invalid_page",
+            format!("page must be between 1 and {}", MAX_SEARCH_PAGE),
+        ));
+    }
+
+    if let Some(year) = search_query.year {
+        if !(1800..=2200).contains(&year) {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "invalid_year",
+                format!("{} is not a plausible release year", year),
+            ));
+        }
+    }
+
+    let language = match &search_query.language {
+        None => request_language(&req),
+        Some(language) => match normalize_language(language) {
+            Some(language) => language,
+            None => {
+                return HttpResponse::BadRequest().json(ErrorResponse::new(
+                    "invalid_language",
+                    format!("{} is not an ISO 639-1 language code", language),
+                ))
+            }
+        },
+    };
+
+    match catalog
+        .search_by_title(&title, search_query.year, page, &language)
+        .await
+    {
+        Ok(results) => with_images(catalog.get_ref(), results).await,
+        Err(err) => tmdb_error_response(&err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KeywordQuery {
+    query: Option<String>,
+    page: Option<u32>,
+}
+
+#[get("/keywords")]
+async fn search_keywords(
+    query: web::Query<KeywordQuery>,
+    catalog: web::Data<dyn MovieCatalog>,
+) -> impl Responder {
+    let keyword = match query.query.as_deref().map(str::trim) {
+        Some(keyword) if !keyword.is_empty() => keyword.to_string(),
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "missing_query",
+                String::from("query must not be empty"),
+            ))
+        }
+    };
+
+    let page = query.page.unwrap_or(1);
+    if !(1..=MAX_SEARCH_PAGE).contains(&page) {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(
+            "invalid_page",
+            format!("page must be between 1 and {}", MAX_SEARCH_PAGE),
+        ));
+    }
+
+    match catalog.search_keywords(&keyword, page).await {
+        Ok(results) => HttpResponse::Ok().json(results),
+        Err(err) => tmdb_error_response(&err),
+    }
+}
+
+#[get("/movie/{movie_id}")]
+async fn get_movie_details(
+    movie_id: web::Path<i64>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
+) -> impl Responder {
+    match catalog
+        .get_movie_details(&movie_id.into_inner(), &request_language(&req))
+        .await
+    {
+        Ok(details) => with_images(catalog.get_ref(), details).await,
+        Err(err) => tmdb_error_response(&err),
+    }
+}
+
+#[get("/tv/{show_id}")]
+async fn get_tv_details(
+    show_id: web::Path<i64>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
+) -> impl Responder {
+    match catalog
+        .get_tv_details(&show_id.into_inner(), &request_language(&req))
+        .await
+    {
+        Ok(details) => with_images(catalog.get_ref(), details).await,
+        Err(err) => tmdb_error_response(&err),
+    }
+}
+
+#[get("/movie/{movie_id}/credits")]
+async fn get_movie_credits(
+    movie_id: web::Path<i64>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
+) -> impl Responder {
+    match catalog
+        .get_credits_for_id(&movie_id.into_inner(), &request_language(&req))
         .await
-        .expect("Uh oh ");
+    {
+        Ok(credits) => HttpResponse::Ok().json(credits),
+        Err(err) => tmdb_error_response(&err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionQuery {
+    session_id: Option<String>,
+}
+
+#[get("/movie/{movie_id}/more_like_this")]
+async fn get_more_like_this(
+    movie_id: web::Path<i64>,
+    query: web::Query<SessionQuery>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
+) -> impl Responder {
+    let session_id = match query.into_inner().session_id {
+        Some(session_id) if !session_id.is_empty() => session_id,
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "missing_session_id",
+                String::from("session_id is required"),
+            ))
+        }
+    };
+
+    match tmdb_helper::get_more_like_this_for_session(
+        catalog.clone().into_inner(),
+        movie_id.into_inner(),
+        session_id,
+        accept_language(&req),
+    )
+    .await
+    {
+        Ok(recommendations) => with_images(catalog.get_ref(), recommendations).await,
+        Err(err) => session_error_response(&err),
+    }
+}
 
-    HttpResponse::Ok().json(movies)
+#[get("/people/{name}")]
+async fn get_people_by_name(
+    name: web::Path<String>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
+) -> impl Responder {
+    match catalog.search_people(&name, &request_language(&req)).await {
+        Ok(people) => HttpResponse::Ok().json(people.results),
+        Err(err) => tmdb_error_response(&err),
+    }
 }
 
 #[post("/decades/{session_id}")]
@@ -133,8 +588,7 @@ async fn post_decades(
     let decade = Decade::from_string(&decade.decade);
 
     match redis_helper::criteria_from_cache(&session_id).await {
-        Err(err) => HttpResponse::InternalServerError()
-            .json(format!("Error fetching session {} : {}", session_id, err)),
+        Err(err) => session_error_response(&err),
         Ok(mut criteria) => {
             criteria.decade = Some(decade);
 
@@ -146,9 +600,251 @@ async fn post_decades(
 
                     HttpResponse::Ok().body(response)
                 }
-                Err(err) => {
-                    HttpResponse::InternalServerError().body(err.detail().unwrap().to_string())
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/region/{session_id}")]
+async fn post_region(
+    session_id: web::Path<String>,
+    region: web::Json<RegionResponse>,
+) -> impl Responder {
+    let id = session_id.clone();
+
+    let region = match normalize_region(&region.region) {
+        Some(region) => region,
+        None => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "invalid_region",
+                format!("{} is not an ISO 3166-1 country code", region.region),
+            ))
+        }
+    };
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.region = Some(region);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted region for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
                 }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/language/{session_id}")]
+async fn post_language(
+    session_id: web::Path<String>,
+    language: web::Json<LanguageResponse>,
+) -> impl Responder {
+    let id = session_id.clone();
+
+    let language = match normalize_language(&language.language) {
+        Some(language) => language,
+        None => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "invalid_language",
+                format!("{} is not an ISO 639-1 language code", language.language),
+            ))
+        }
+    };
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.language = Some(language);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted language for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/quality/{session_id}")]
+async fn post_quality(
+    session_id: web::Path<String>,
+    quality: web::Json<QualityResponse>,
+) -> impl Responder {
+    let id = session_id.clone();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.quality = Some(quality.into_inner().quality);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted quality for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+/* Bounds posted here override the quality preset's one by one - post an empty object to go back to the preset */
+#[post("/vote_bounds/{session_id}")]
+async fn post_vote_bounds(
+    session_id: web::Path<String>,
+    vote_bounds: web::Json<VoteBounds>,
+) -> impl Responder {
+    let id = session_id.clone();
+    let vote_bounds = vote_bounds.into_inner();
+
+    if !vote_bounds.is_valid() {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(
+            "invalid_vote_bounds",
+            String::from(
+                "vote averages must be between 0 and 10, vote counts can't be negative and minimums can't be above maximums",
+            ),
+        ));
+    }
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.vote_bounds = Some(vote_bounds);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted vote bounds for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/sort/{session_id}")]
+async fn post_sort(session_id: web::Path<String>, sort: web::Json<SortResponse>) -> impl Responder {
+    let id = session_id.clone();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.sort = Some(sort.into_inner().sort);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted sort for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/diversity/{session_id}")]
+async fn post_diversity(
+    session_id: web::Path<String>,
+    diversity: web::Json<DiversityResponse>,
+) -> impl Responder {
+    let id = session_id.clone();
+    let diversity = diversity.into_inner().diversity;
+
+    if !(0.0..=1.0).contains(&diversity) {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(
+            "invalid_diversity",
+            String::from("diversity must be between 0 and 1"),
+        ));
+    }
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.diversity = Some(diversity);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted diversity for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/monetization_types/{session_id}")]
+async fn post_monetization_types(
+    session_id: web::Path<String>,
+    monetization_types: web::Json<Vec<MonetizationType>>,
+) -> impl Responder {
+    let id = session_id.clone();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.monetization_types = Some(monetization_types.into_inner());
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted monetization types for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/media_type/{session_id}")]
+async fn post_media_type(
+    session_id: web::Path<String>,
+    media_type: web::Json<MediaTypeResponse>,
+) -> impl Responder {
+    let id = session_id.clone();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.media_type = Some(media_type.into_inner().media_type);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted media type for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
             }
         }
     }
@@ -162,8 +858,7 @@ async fn post_providers(
     let id = session_id.clone();
 
     match redis_helper::criteria_from_cache(&session_id).await {
-        Err(err) => HttpResponse::InternalServerError()
-            .json(format!("Error fetching session {} : {}", session_id, err)),
+        Err(err) => session_error_response(&err),
         Ok(mut criteria) => {
             criteria.watch_providers = Some(providers.into_inner());
 
@@ -175,9 +870,7 @@ async fn post_providers(
 
                     HttpResponse::Ok().body(response)
                 }
-                Err(err) => {
-                    HttpResponse::InternalServerError().body(err.detail().unwrap().to_string())
-                }
+                Err(err) => session_error_response(&err.into()),
             }
         }
     }
@@ -191,8 +884,7 @@ async fn post_genres(
     let id = session_id.clone();
 
     match redis_helper::criteria_from_cache(&session_id).await {
-        Err(err) => HttpResponse::InternalServerError()
-            .json(format!("Error fetching session {} : {}", session_id, err)),
+        Err(err) => session_error_response(&err),
         Ok(mut criteria) => {
             criteria.genres = Some(genres.into_inner());
 
@@ -204,9 +896,53 @@ async fn post_genres(
 
                     HttpResponse::Ok().body(response)
                 }
-                Err(err) => {
-                    HttpResponse::InternalServerError().body(err.detail().unwrap().to_string())
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/cast/{session_id}")]
+async fn post_cast(session_id: web::Path<String>, cast: web::Json<Vec<Person>>) -> impl Responder {
+    let id = session_id.clone();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.with_cast = Some(cast.into_inner());
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted cast for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
+}
+
+#[post("/crew/{session_id}")]
+async fn post_crew(session_id: web::Path<String>, crew: web::Json<Vec<Person>>) -> impl Responder {
+    let id = session_id.clone();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria.with_crew = Some(crew.into_inner());
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted crew for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
                 }
+                Err(err) => session_error_response(&err.into()),
             }
         }
     }
@@ -221,8 +957,7 @@ async fn post_runtime(
     println!("Received a runtime: {:#?}", runtime);
 
     match redis_helper::criteria_from_cache(&session_id).await {
-        Err(err) => HttpResponse::InternalServerError()
-            .json(format!("Error fetching session {} : {}", session_id, err)),
+        Err(err) => session_error_response(&err),
         Ok(mut criteria) => {
             criteria.runtime = Some(runtime.into_inner().runtime);
 
@@ -232,85 +967,125 @@ async fn post_runtime(
 
                     HttpResponse::Ok().body(response)
                 }
-                Err(err) => {
-                    HttpResponse::InternalServerError().body(err.detail().unwrap().to_string())
-                }
+                Err(err) => session_error_response(&err.into()),
             }
         }
     }
 }
 
+/* Merges a round of keyword votes into the session's profile - like and dislike are re-derived from it, must_have and avoid are left alone */
 fn update_feedback(
     mut criteria: RecommendationCriteria,
-    mut feedback: Feedback,
+    votes: &HashMap<i64, f64>,
+    decay: f64,
 ) -> RecommendationCriteria {
-    if let Some(mut criteria_feedback) = criteria.feedback.take() {
-        if let Some(mut likes) = criteria_feedback.like.take() {
-            if let Some(feedback_likes) = feedback.like.take() {
-                likes.extend(feedback_likes);
-                criteria_feedback.like = Some(likes);
-            } else {
-                criteria_feedback.like = Some(likes);
-            }
-        } else {
-            criteria_feedback.like = feedback.like;
-        }
-        if let Some(mut dislikes) = criteria_feedback.dislike.take() {
-            if let Some(feedback_dislikes) = feedback.dislike.take() {
-                dislikes.extend(feedback_dislikes);
-                criteria_feedback.dislike = Some(dislikes);
-            } else {
-                criteria_feedback.dislike = Some(dislikes);
+    criteria
+        .feedback
+        .get_or_insert_with(Feedback::default)
+        .record_votes(votes, decay);
+
+    criteria
+}
+
+#[post("/keywords/{session_id}")]
+async fn post_keyword_preference(
+    session_id: web::Path<String>,
+    keyword_preference: web::Json<KeywordPreferenceResponse>,
+) -> impl Responder {
+    let id = session_id.clone();
+    let keyword_preference = keyword_preference.into_inner();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            criteria
+                .feedback
+                .get_or_insert_with(Feedback::default)
+                .add_keyword_preference(keyword_preference.keyword, keyword_preference.preference);
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Posted keyword preference for {}", id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
             }
-        } else {
-            criteria_feedback.dislike = feedback.dislike;
         }
-        criteria.feedback = Some(criteria_feedback);
-    } else {
-        criteria.feedback = Some(feedback);
     }
+}
 
-    criteria
+#[delete("/keywords/{session_id}/{keyword_id}")]
+async fn delete_keyword_preference(path: web::Path<(String, i64)>) -> impl Responder {
+    let (session_id, keyword_id) = path.into_inner();
+
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Err(err) => session_error_response(&err),
+        Ok(mut criteria) => {
+            let removed = criteria
+                .feedback
+                .as_mut()
+                .map(|feedback| feedback.remove_keyword_preference(keyword_id))
+                .unwrap_or(false);
+
+            if !removed {
+                return HttpResponse::NotFound().json(ErrorResponse::new(
+                    "not_found",
+                    format!(
+                        "Keyword {} isn't a preference for {}",
+                        keyword_id, session_id
+                    ),
+                ));
+            }
+
+            match redis_helper::criteria_to_cache(&session_id, criteria).await {
+                Ok(redis_response) => {
+                    let response = format!("Removed keyword {} for {}", keyword_id, session_id);
+
+                    println!("{}", &response);
+
+                    HttpResponse::Ok().body(response)
+                }
+                Err(err) => session_error_response(&err.into()),
+            }
+        }
+    }
 }
 
 #[post("/feedback/{session_id}")]
 async fn post_feedback(
     session_id: web::Path<String>,
     feedback: web::Json<Feedback>,
-    tmdb: web::Data<Tmdb>,
+    catalog: web::Data<dyn MovieCatalog>,
+    feedback_config: web::Data<config::FeedbackConfig>,
 ) -> impl Responder {
-    let tmdb = tmdb.into_inner();
+    let catalog = catalog.into_inner();
     let feedback = feedback.into_inner();
     match redis_helper::criteria_from_cache(&session_id).await {
-        Err(err) => HttpResponse::InternalServerError()
-            .json(format!("Error fetching session {} : {}", session_id, err)),
+        Err(err) => session_error_response(&err),
         Ok(criteria) => {
-            let (upvotes, downvotes) = tmdb_helper::process_feedback(
-                tmdb,
-                feedback.like.unwrap(),
-                feedback.dislike.unwrap(),
-            )
-            .await;
-
-            let feedback = Feedback {
-                like: match upvotes.is_empty() {
-                    true => None,
-                    false => Some(upvotes),
-                },
-                dislike: match downvotes.is_empty() {
-                    true => None,
-                    false => Some(downvotes),
-                },
-            };
-
-            let criteria = update_feedback(criteria, feedback);
+            let like = feedback.like.unwrap_or_default();
+            let dislike = feedback.dislike.unwrap_or_default();
+            let media_type = criteria.media_type();
+
+            let votes =
+                tmdb_helper::process_feedback(catalog, media_type, like.clone(), dislike.clone())
+                    .await;
+
+            let criteria = update_feedback(criteria, &votes, feedback_config.keyword_decay);
+
+            // Anything voted on has been seen, whether or not it came from a recommendation batch
+            let voted: Vec<i64> = like.into_iter().chain(dislike).collect();
+            if let Err(err) = redis_helper::mark_seen(&session_id, media_type, &voted).await {
+                println!("Error saving seen titles for {}: {}", session_id, err);
+            }
 
             println!("Posting feedback");
 
             match redis_helper::criteria_to_cache(&session_id, criteria).await {
-                Err(err) => {
-                    HttpResponse::InternalServerError().body(err.detail().unwrap().to_string())
-                }
+                Err(err) => session_error_response(&err.into()),
                 Ok(redis_response) => HttpResponse::Ok().body("Posted feedback"),
             }
         }
@@ -319,64 +1094,139 @@ async fn post_feedback(
 
 #[get("/session_criteria/{session_id}")]
 async fn get_session_criteria(session_id: web::Path<String>) -> impl Responder {
-    let criteria = redis_helper::criteria_from_cache(&session_id)
-        .await
-        .expect("Uh oh");
+    match redis_helper::criteria_from_cache(&session_id).await {
+        Ok(criteria) => HttpResponse::Ok().json(criteria),
+        Err(err) => session_error_response(&err),
+    }
+}
 
-    HttpResponse::Ok().json(criteria)
+/* The batch is still worth returning if it can't be remembered */
+async fn mark_shown(session_id: &str, media_type: MediaType, ids: &[i64]) {
+    if let Err(err) = redis_helper::mark_seen(session_id, media_type, ids).await {
+        println!("Error saving seen titles for {}: {}", session_id, err);
+    }
 }
 
+#[derive(Debug, Deserialize)]
+struct RecommendQuery {
+    sort: Option<SortBy>,
+}
+
+/* ?sort= reorders this batch without changing the session's own sort */
+/* Everything sent back is remembered as seen, so the next batch moves on to new titles */
 #[get("/recommend/{session_id}")]
 async fn get_recommendations(
     session_id: web::Path<String>,
-    tmdb: web::Data<Tmdb>,
+    query: web::Query<RecommendQuery>,
+    catalog: web::Data<dyn MovieCatalog>,
+    req: HttpRequest,
 ) -> impl Responder {
-    let tmdb = tmdb.into_inner();
-
-    match tmdb_helper::get_recommendations_for_session(tmdb, session_id.into_inner()).await {
-        Err(err) => HttpResponse::InternalServerError()
-            .json(format!("Error fetching recommendations for ID: {}", err)),
-        Ok(recs) => {
+    let catalog = catalog.into_inner();
+    let session_id = session_id.into_inner();
+
+    match tmdb_helper::get_recommendations_for_session(
+        Arc::clone(&catalog),
+        session_id.clone(),
+        accept_language(&req),
+        query.sort,
+    )
+    .await
+    {
+        Err(err) => session_error_response(&err),
+        Ok((criteria, tmdb_helper::SessionRecommendations::Movies(recs))) => {
             let mut movie_recommendations: Vec<MovieRecommendation> = vec![];
 
             for rec in recs {
-                let providers: Vec<WatchProvider> = rec
-                    .async_providers
+                // A movie whose providers can't be checked is skipped rather than failing the batch
+                let Some(providers) =
+                    tmdb_helper::await_lookup(rec.async_providers, "watch providers", rec.movie.id)
+                        .await
+                else {
+                    continue;
+                };
+                // A trailer is nice to have, so failing to find one only gets logged
+                let trailer = tmdb_helper::await_lookup(rec.async_trailer, "trailer", rec.movie.id)
                     .await
-                    .expect(format!("Error fetching watch providers for {}", rec.movie.id).as_str())
-                    .results
-                    .us
-                    .flatrate;
+                    .flatten();
+                let keywords = tmdb_helper::await_keywords(rec.keywords, rec.movie.id).await;
+                // Discover filters on runtime, but without the movie's details it can't be shown
+                let explanation =
+                    Explanation::new(&criteria, &rec.movie, &keywords, &providers, None);
                 movie_recommendations.push(MovieRecommendation {
                     movie: rec.movie,
                     providers,
+                    trailer,
+                    explanation,
+                })
+            }
+
+            let shown: Vec<i64> = movie_recommendations
+                .iter()
+                .map(|rec| rec.movie.id)
+                .collect();
+            mark_shown(&session_id, MediaType::Movie, &shown).await;
+
+            with_images(catalog.as_ref(), movie_recommendations).await
+        }
+        Ok((_, tmdb_helper::SessionRecommendations::Tv(recs))) => {
+            let mut tv_recommendations: Vec<TvRecommendation> = vec![];
+
+            for rec in recs {
+                let Some(providers) =
+                    tmdb_helper::await_lookup(rec.async_providers, "watch providers", rec.show.id)
+                        .await
+                else {
+                    continue;
+                };
+                tv_recommendations.push(TvRecommendation {
+                    show: rec.show,
+                    providers,
                 })
             }
 
-            HttpResponse::Ok().json(movie_recommendations)
+            let shown: Vec<i64> = tv_recommendations.iter().map(|rec| rec.show.id).collect();
+            mark_shown(&session_id, MediaType::Tv, &shown).await;
+
+            with_images(catalog.as_ref(), tv_recommendations).await
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct MediaTypeQuery {
+    media_type: Option<MediaType>,
+}
+
 #[get("/genres")]
-async fn get_genres(tmdb: web::Data<Tmdb>) -> impl Responder {
-    let tmdb = tmdb.into_inner();
+async fn get_genres(
+    catalog: web::Data<dyn MovieCatalog>,
+    query: web::Query<MediaTypeQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    let catalog = catalog.into_inner();
+    let language = request_language(&req);
 
-    let genre_list = tmdb.get_genre_list().await;
+    let genres = match query.media_type.unwrap_or_default() {
+        MediaType::Movie => catalog.get_genre_list(&language).await,
+        MediaType::Tv => catalog.get_tv_genre_list(&language).await,
+    };
 
-    match tmdb.get_genre_list().await {
+    match genres {
         Ok(list) => HttpResponse::Ok().json(list.genres),
-        Err(err) => {
-            HttpResponse::InternalServerError().json(format!("Error fetching genres: {}", err))
-        }
+        Err(err) => tmdb_error_response(&err),
     }
 }
 
+#[get("/cache_stats")]
+async fn get_cache_stats(catalog: web::Data<dyn MovieCatalog>) -> impl Responder {
+    HttpResponse::Ok().json(catalog.cache_stats())
+}
+
 #[get{"/start_session"}]
 async fn start_session() -> impl Responder {
     println!("Got request to start session");
     match redis_helper::start_recommendation_session().await {
-        Err(err) => HttpResponse::InternalServerError().body(err.detail().unwrap().to_string()),
+        Err(err) => session_error_response(&err.into()),
         Ok(session_id) => HttpResponse::Ok().body(session_id),
     }
 }
@@ -395,190 +1245,213 @@ mod tests {
                 provider_id: 434,
                 provider_name: "foo".to_string(),
                 logo_path: "/".to_string(),
+                logo_url: None,
             }]),
             runtime: Some(Runtime::Average),
             decade: Some(Decade::Recent),
             feedback: None,
+            region: None,
+            monetization_types: None,
+            with_cast: None,
+            with_crew: None,
+            media_type: None,
+            language: None,
+            quality: None,
+            vote_bounds: None,
+            sort: None,
+            seen: None,
+            diversity: None,
         }
     }
 
     #[test]
-    fn test_update_feedback_criteria() {
+    fn test_update_feedback_merges_rounds() {
         let mut criteria = get_criteria();
 
-        let criteria_feedback = Feedback {
-            like: Some(vec![222, 444, 666]),
-            dislike: Some(vec![111, 333, 555]),
-        };
-
-        let new_feedback = Feedback {
-            like: Some(vec![888, 1010, 1212]),
-            dislike: Some(vec![777, 999, 1111]),
-        };
-
-        criteria.feedback = Some(criteria_feedback);
-
-        let updated_criteria = update_feedback(criteria, new_feedback);
+        criteria.feedback = Some(Feedback {
+            must_have: Some(vec![Keyword {
+                id: 9715,
+                name: "superhero".to_string(),
+            }]),
+            ..Feedback::default()
+        });
 
-        assert!(updated_criteria.feedback.is_some());
+        let first_round = HashMap::from([(222, 2.0), (444, 1.0), (111, -1.0)]);
+        let second_round = HashMap::from([(222, 1.0), (666, 1.0), (111, -2.0), (444, -1.0)]);
 
-        let updated_feedback = updated_criteria.feedback.unwrap();
+        let criteria = update_feedback(criteria, &first_round, 1.0);
+        let criteria = update_feedback(criteria, &second_round, 1.0);
 
-        assert_eq!(
-            updated_feedback.like.unwrap(),
-            vec![222, 444, 666, 888, 1010, 1212]
-        );
+        let feedback = criteria.feedback.unwrap();
+        let profile = feedback.keyword_profile.unwrap();
 
+        // Votes add up across rounds instead of piling up duplicate IDs, and 444 cancelled out
+        assert_eq!(profile.rounds, 2);
         assert_eq!(
-            updated_feedback.dislike.unwrap(),
-            vec![111, 333, 555, 777, 999, 1111]
+            profile.weights,
+            HashMap::from([(222, 3.0), (666, 1.0), (111, -3.0)])
         );
+        assert_eq!(feedback.like.unwrap(), vec![222, 666]);
+        assert_eq!(feedback.dislike.unwrap(), vec![111]);
+        assert!(feedback.must_have.is_some());
     }
 
     #[test]
-    fn test_update_feedback_criteria_empty_likes() {
-        let mut criteria = get_criteria();
-
-        let criteria_feedback = Feedback {
-            like: None,
-            dislike: Some(vec![111, 333, 555]),
-        };
-
-        let new_feedback = Feedback {
-            like: Some(vec![888, 1010, 1212]),
-            dislike: Some(vec![777, 999, 1111]),
-        };
-
-        criteria.feedback = Some(criteria_feedback);
-
-        let updated_criteria = update_feedback(criteria, new_feedback);
-
-        assert!(updated_criteria.feedback.is_some());
-
-        let updated_feedback = updated_criteria.feedback.unwrap();
+    fn test_update_feedback_decay() {
+        let criteria = update_feedback(get_criteria(), &HashMap::from([(222, 2.0)]), 0.5);
+        let criteria = update_feedback(criteria, &HashMap::from([(444, 1.5)]), 0.5);
 
-        assert_eq!(updated_feedback.like.unwrap(), vec![888, 1010, 1212]);
+        let feedback = criteria.feedback.unwrap();
 
-        assert_eq!(
-            updated_feedback.dislike.unwrap(),
-            vec![111, 333, 555, 777, 999, 1111]
-        );
+        // The older vote halved to 1.0, so the newer one comes first
+        assert_eq!(feedback.like.unwrap(), vec![444, 222]);
+        assert_eq!(feedback.dislike, None);
     }
 
     #[test]
-    fn test_update_feedback_criteria_empty_dislikes() {
+    fn test_update_feedback_without_profile() {
         let mut criteria = get_criteria();
 
-        let criteria_feedback = Feedback {
-            like: Some(vec![222, 444, 666]),
-            dislike: None,
-        };
-
-        let new_feedback = Feedback {
-            like: Some(vec![888, 1010, 1212]),
-            dislike: Some(vec![777, 999, 1111]),
-        };
+        // Sessions from before the profile was kept carry on from their like and dislike lists
+        criteria.feedback = Some(Feedback {
+            like: Some(vec![222, 444]),
+            dislike: Some(vec![111]),
+            ..Feedback::default()
+        });
 
-        criteria.feedback = Some(criteria_feedback);
+        let criteria = update_feedback(criteria, &HashMap::from([(444, 2.0), (111, 1.0)]), 1.0);
 
-        let updated_criteria = update_feedback(criteria, new_feedback);
-
-        assert!(updated_criteria.feedback.is_some());
-
-        let updated_feedback = updated_criteria.feedback.unwrap();
+        let feedback = criteria.feedback.unwrap();
 
         assert_eq!(
-            updated_feedback.like.unwrap(),
-            vec![222, 444, 666, 888, 1010, 1212]
+            feedback.keyword_profile.unwrap().weights,
+            HashMap::from([(222, 1.0), (444, 3.0)])
         );
-
-        assert_eq!(updated_feedback.dislike.unwrap(), vec![777, 999, 1111]);
+        assert_eq!(feedback.like.unwrap(), vec![444, 222]);
+        assert_eq!(feedback.dislike, None);
     }
 
     #[test]
-    fn test_update_feedback_empty_likes() {
-        let mut criteria = get_criteria();
-
-        let criteria_feedback = Feedback {
-            like: Some(vec![222, 444, 666]),
-            dislike: Some(vec![111, 333, 555]),
-        };
-
-        let new_feedback = Feedback {
-            like: None,
-            dislike: Some(vec![777, 999, 1111]),
-        };
-
-        criteria.feedback = Some(criteria_feedback);
+    fn test_request_language() {
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("Accept-Language", "de;q=0.5, fr-CA, *;q=0.1"))
+            .to_http_request();
 
-        let updated_criteria = update_feedback(criteria, new_feedback);
+        assert_eq!(request_language(&req), "fr-CA");
 
-        assert!(updated_criteria.feedback.is_some());
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("Accept-Language", "*"))
+            .to_http_request();
 
-        let updated_feedback = updated_criteria.feedback.unwrap();
-
-        assert_eq!(updated_feedback.like.unwrap(), vec![222, 444, 666]);
-
-        assert_eq!(
-            updated_feedback.dislike.unwrap(),
-            vec![111, 333, 555, 777, 999, 1111]
-        );
+        assert_eq!(accept_language(&req), None);
+        assert_eq!(request_language(&req), DEFAULT_LANGUAGE);
     }
 
-    #[test]
-    fn test_update_feedback_empty_dislikes() {
-        let mut criteria = get_criteria();
-
-        let criteria_feedback = Feedback {
-            like: Some(vec![222, 444, 666]),
-            dislike: Some(vec![111, 333, 555]),
-        };
-
-        let new_feedback = Feedback {
-            like: Some(vec![888, 1010, 1212]),
-            dislike: None,
-        };
-
-        criteria.feedback = Some(criteria_feedback);
-
-        let updated_criteria = update_feedback(criteria, new_feedback);
-
-        assert!(updated_criteria.feedback.is_some());
-
-        let updated_feedback = updated_criteria.feedback.unwrap();
-
-        assert_eq!(
-            updated_feedback.like.unwrap(),
-            vec![222, 444, 666, 888, 1010, 1212]
+    #[actix_web::test]
+    async fn test_search_movies() {
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(
+            InMemoryCatalog::new()
+                .with_movie(Movie {
+                    id: 78,
+                    overview: String::new(),
+                    popularity: 0.0,
+                    poster_path: None,
+                    poster_url: None,
+                    release_date: "1982-06-25".to_string(),
+                    title: "Blade Runner".to_string(),
+                    vote_average: 0.0,
+                    vote_count: 0,
+                    genre_ids: vec![],
+                })
+                .with_movie(Movie {
+                    id: 335984,
+                    overview: String::new(),
+                    popularity: 0.0,
+                    poster_path: None,
+                    poster_url: None,
+                    release_date: "2017-10-04".to_string(),
+                    title: "Blade Runner 2049".to_string(),
+                    vote_average: 0.0,
+                    vote_count: 0,
+                    genre_ids: vec![],
+                }),
         );
 
-        assert_eq!(updated_feedback.dislike.unwrap(), vec![111, 333, 555]);
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::from(catalog))
+                .service(search_movies),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/search?query=blade%20runner&year=2017")
+            .to_request();
+        let results: SearchByTitleResponse =
+            actix_web::test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(results.page, 1);
+        assert_eq!(results.total_results, 1);
+        assert_eq!(results.results[0].id, 335984);
+
+        for uri in [
+            "/search",
+            "/search?query=%20",
+            "/search?query=blade&page=0",
+            "/search?query=blade&page=501",
+            "/search?query=blade&language=klingon",
+        ] {
+            let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+            let response = actix_web::test::call_service(&app, req).await;
+
+            assert_eq!(response.status(), 400, "{}", uri);
+        }
     }
 
-    #[test]
-    fn test_update_feedback_empty_criteria() {
-        let mut criteria = get_criteria();
-
-        let criteria_feedback = Feedback {
-            like: None,
-            dislike: None,
-        };
-
-        let new_feedback = Feedback {
-            like: Some(vec![888, 1010, 1212]),
-            dislike: Some(vec![777, 999, 1111]),
-        };
+    #[actix_web::test]
+    async fn test_recommend_rejects_unknown_sort() {
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(InMemoryCatalog::new());
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::from(catalog))
+                .service(get_recommendations),
+        )
+        .await;
+
+        // The query is rejected before the session is ever looked up
+        let req = actix_web::test::TestRequest::get()
+            .uri("/recommend/some-session?sort=alphabetical")
+            .to_request();
+        let response = actix_web::test::call_service(&app, req).await;
+
+        assert_eq!(response.status(), 400);
+    }
 
-        criteria.feedback = Some(criteria_feedback);
+    #[test]
+    fn test_session_error_response() {
+        let not_found = tmdb_helper::SessionError::NotFound("some-session".to_string());
+        assert_eq!(session_error_response(&not_found).status(), 404);
 
-        let updated_criteria = update_feedback(criteria, new_feedback);
+        let incomplete = tmdb_helper::SessionError::Incomplete("genres");
+        assert_eq!(session_error_response(&incomplete).status(), 409);
 
-        assert!(updated_criteria.feedback.is_some());
+        let invalid = serde_json::from_str::<RecommendationCriteria>("{\"genres\":1}")
+            .map_err(tmdb_helper::SessionError::Invalid)
+            .unwrap_err();
+        assert_eq!(session_error_response(&invalid).status(), 500);
+    }
 
-        let updated_feedback = updated_criteria.feedback.unwrap();
+    #[actix_web::test]
+    async fn test_post_diversity_out_of_range() {
+        let app = actix_web::test::init_service(App::new().service(post_diversity)).await;
 
-        assert_eq!(updated_feedback.like.unwrap(), vec![888, 1010, 1212]);
+        // Checked before the session is ever looked up
+        let req = actix_web::test::TestRequest::post()
+            .uri("/diversity/some-session")
+            .set_json(DiversityResponse { diversity: 1.5 })
+            .to_request();
+        let response = actix_web::test::call_service(&app, req).await;
 
-        assert_eq!(updated_feedback.dislike.unwrap(), vec![777, 999, 1111]);
+        assert_eq!(response.status(), 400);
     }
 }
@@ -0,0 +1,356 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::*;
+
+/* How much each signal counts towards a candidate's score */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankingWeights {
+    /* Per unit of keyword weight, after scaling by the candidate's keyword count */
+    pub keyword: f64,
+    /* For matching every picked genre */
+    pub genre: f64,
+    /* For being TMDB's most popular candidate, falling off linearly to nothing for the least popular */
+    pub popularity: f64,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        Self {
+            keyword: 1.0,
+            genre: 0.5,
+            popularity: 0.25,
+        }
+    }
+}
+
+/* What a session has told us it likes, in a form candidates can be scored against */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preferences {
+    pub keyword_weights: HashMap<i64, f64>,
+    pub genre_ids: HashSet<i32>,
+}
+
+impl Preferences {
+    /* Sessions from before the keyword profile was kept fall back to like and dislike */
+    pub fn from_criteria(criteria: &RecommendationCriteria) -> Self {
+        let keyword_weights = match &criteria.feedback {
+            Some(feedback) => match &feedback.keyword_profile {
+                Some(profile) => profile.weights.clone(),
+                None => KeywordProfile::from_ids(&feedback.like, &feedback.dislike).weights,
+            },
+            None => HashMap::new(),
+        };
+
+        Self {
+            keyword_weights,
+            genre_ids: criteria
+                .genres
+                .iter()
+                .flatten()
+                .map(|genre| genre.id)
+                .collect(),
+        }
+    }
+
+    /* Nothing to score on, so TMDB's order is as good as any */
+    pub fn is_empty(&self) -> bool {
+        self.keyword_weights.is_empty() && self.genre_ids.is_empty()
+    }
+}
+
+/* Learned keywords are scored rather than filtered on, so they're left out of the discover query */
+/* Keywords the user picked explicitly (must_have and avoid) are still hard filters */
+pub fn candidate_criteria(criteria: &RecommendationCriteria) -> RecommendationCriteria {
+    let mut candidate_criteria = criteria.clone();
+
+    if let Some(feedback) = candidate_criteria.feedback.as_mut() {
+        feedback.like = None;
+        feedback.dislike = None;
+    }
+
+    candidate_criteria
+}
+
+/* Share of two candidates' similarity that comes from keyword overlap - the rest comes from genre overlap */
+const KEYWORD_SIMILARITY_SHARE: f64 = 0.7;
+
+/* A discover result along with what it's scored on */
+#[derive(Debug, Clone)]
+pub struct Candidate<T> {
+    pub item: T,
+    pub keyword_ids: Vec<i64>,
+    pub genre_ids: Vec<i32>,
+    /* Only looked up when diversifying, and only movies have collections */
+    pub collection_id: Option<i64>,
+}
+
+/* Shared items over all items - nothing in common, or nothing to compare, is 0 */
+fn jaccard<I: Eq + std::hash::Hash + Copy>(a: &[I], b: &[I]) -> f64 {
+    let a: HashSet<I> = a.iter().copied().collect();
+    let b: HashSet<I> = b.iter().copied().collect();
+
+    match a.union(&b).count() {
+        0 => 0.0,
+        union => a.intersection(&b).count() as f64 / union as f64,
+    }
+}
+
+/* From 0 to 1 - entries in the same collection are as alike as it gets, however different their keywords */
+pub fn similarity<T>(a: &Candidate<T>, b: &Candidate<T>) -> f64 {
+    if a.collection_id.is_some() && a.collection_id == b.collection_id {
+        return 1.0;
+    }
+
+    KEYWORD_SIMILARITY_SHARE * jaccard(&a.keyword_ids, &b.keyword_ids)
+        + (1.0 - KEYWORD_SIMILARITY_SHARE) * jaccard(&a.genre_ids, &b.genre_ids)
+}
+
+/* position is the candidate's place in TMDB's popularity order out of pool_size */
+pub fn score<T>(
+    candidate: &Candidate<T>,
+    position: usize,
+    pool_size: usize,
+    preferences: &Preferences,
+    weights: &RankingWeights,
+) -> f64 {
+    // Scaled by the square root so movies with dozens of keywords don't win on volume alone
+    let keyword_score = match candidate.keyword_ids.len() {
+        0 => 0.0,
+        count => {
+            candidate
+                .keyword_ids
+                .iter()
+                .filter_map(|id| preferences.keyword_weights.get(id))
+                .sum::<f64>()
+                / (count as f64).sqrt()
+        }
+    };
+
+    let genre_score = match preferences.genre_ids.len() {
+        0 => 0.0,
+        count => {
+            candidate
+                .genre_ids
+                .iter()
+                .filter(|id| preferences.genre_ids.contains(id))
+                .count() as f64
+                / count as f64
+        }
+    };
+
+    let popularity_score = 1.0 - position as f64 / pool_size.max(1) as f64;
+
+    weights.keyword * keyword_score
+        + weights.genre * genre_score
+        + weights.popularity * popularity_score
+}
+
+/* Best count candidates by score, spread out by diversity - see diversify */
+/* Candidates come in TMDB's popularity order, which also breaks ties */
+pub fn rank<T>(
+    candidates: Vec<Candidate<T>>,
+    preferences: &Preferences,
+    weights: &RankingWeights,
+    diversity: f64,
+    count: usize,
+) -> Vec<T> {
+    let pool_size = candidates.len();
+
+    let mut scored: Vec<(f64, Candidate<T>)> = candidates
+        .into_iter()
+        .enumerate()
+        .map(|(position, candidate)| {
+            (
+                score(&candidate, position, pool_size, preferences, weights),
+                candidate,
+            )
+        })
+        .collect();
+
+    // sort_by is stable, so equal scores keep their popularity order
+    scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+
+    if diversity > 0.0 {
+        return diversify(scored, diversity, count);
+    }
+
+    scored
+        .into_iter()
+        .take(count)
+        .map(|(_, candidate)| candidate.item)
+        .collect()
+}
+
+/* Maximal marginal relevance - each pick is the candidate whose score, less its similarity to what's already been */
+/* picked, is highest. Scores are scaled to 0-1 first, so diversity 0 is plain score order and 1 ignores scores entirely */
+/* Expects candidates best score first */
+pub fn diversify<T>(scored: Vec<(f64, Candidate<T>)>, diversity: f64, count: usize) -> Vec<T> {
+    let (min, max) = scored.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(min, max), (score, _)| (min.min(*score), max.max(*score)),
+    );
+
+    let mut remaining: Vec<(f64, Candidate<T>)> = scored
+        .into_iter()
+        .map(|(score, candidate)| match max > min {
+            true => ((score - min) / (max - min), candidate),
+            false => (1.0, candidate),
+        })
+        .collect();
+
+    // Closest each remaining candidate is to anything picked so far
+    let mut max_similarity = vec![0.0; remaining.len()];
+    let mut picked = vec![];
+
+    while picked.len() < count && !remaining.is_empty() {
+        let mut best = 0;
+        let mut best_value = f64::NEG_INFINITY;
+
+        // Strictly greater, so ties go to the better scored candidate
+        for (index, (relevance, _)) in remaining.iter().enumerate() {
+            let value = (1.0 - diversity) * relevance - diversity * max_similarity[index];
+
+            if value > best_value {
+                best = index;
+                best_value = value;
+            }
+        }
+
+        let (_, choice) = remaining.remove(best);
+        max_similarity.remove(best);
+
+        for (index, (_, candidate)) in remaining.iter().enumerate() {
+            max_similarity[index] = max_similarity[index].max(similarity(&choice, candidate));
+        }
+
+        picked.push(choice.item);
+    }
+
+    picked
+}
+
+/* ======================================================================================================================== */
+/* ====================================================== UNIT TESTS ====================================================== */
+/* ======================================================================================================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: i64, keyword_ids: Vec<i64>, genre_ids: Vec<i32>) -> Candidate<i64> {
+        Candidate {
+            item: id,
+            keyword_ids,
+            genre_ids,
+            collection_id: None,
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let preferences = Preferences {
+            keyword_weights: HashMap::from([(10, 3.0), (20, 1.0), (30, -2.0)]),
+            genre_ids: HashSet::from([28, 878]),
+        };
+
+        let ranked = rank(
+            vec![
+                // Most popular, but only has a disliked keyword
+                candidate(1, vec![30, 99], vec![28]),
+                candidate(2, vec![], vec![]),
+                candidate(3, vec![10, 20], vec![28, 878]),
+                candidate(4, vec![20], vec![12]),
+            ],
+            &preferences,
+            &RankingWeights::default(),
+            0.0,
+            3,
+        );
+
+        assert_eq!(ranked, vec![3, 4, 2]);
+    }
+
+    #[test]
+    fn test_rank_without_preferences() {
+        let ranked = rank(
+            (1..=5).map(|id| candidate(id, vec![id], vec![])).collect(),
+            &Preferences::default(),
+            &RankingWeights::default(),
+            0.0,
+            5,
+        );
+
+        assert_eq!(ranked, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_rank_with_diversity() {
+        let in_collection = |id: i64, collection_id: i64| Candidate {
+            collection_id: Some(collection_id),
+            ..candidate(id, vec![], vec![28])
+        };
+
+        // The three most popular are one franchise, the next two share every keyword and genre
+        let candidates = || {
+            vec![
+                in_collection(1, 10),
+                in_collection(2, 10),
+                in_collection(3, 10),
+                candidate(4, vec![100, 200], vec![18]),
+                candidate(5, vec![100, 200], vec![18]),
+                candidate(6, vec![300], vec![35]),
+            ]
+        };
+
+        let rank_with = |diversity: f64| {
+            rank(
+                candidates(),
+                &Preferences::default(),
+                &RankingWeights::default(),
+                diversity,
+                4,
+            )
+        };
+
+        assert_eq!(rank_with(0.0), vec![1, 2, 3, 4]);
+        // A little lets one sequel through before the rest of the franchise drops back
+        assert_eq!(rank_with(0.2), vec![1, 2, 4, 3]);
+        assert_eq!(rank_with(0.5), vec![1, 4, 6, 2]);
+    }
+
+    #[test]
+    fn test_preferences_from_criteria() {
+        let criteria = RecommendationCriteria {
+            genres: Some(vec![Genre {
+                id: 18,
+                name: "Drama".to_string(),
+            }]),
+            feedback: Some(Feedback {
+                like: Some(vec![10, 20]),
+                dislike: Some(vec![20, 30]),
+                ..Feedback::default()
+            }),
+            ..RecommendationCriteria::default()
+        };
+
+        let preferences = Preferences::from_criteria(&criteria);
+
+        assert_eq!(
+            preferences.keyword_weights,
+            HashMap::from([(10, 1.0), (20, 0.0), (30, -1.0)])
+        );
+        assert_eq!(preferences.genre_ids, HashSet::from([18]));
+
+        // Learned keywords move out of discover, explicit ones stay
+        let mut criteria = criteria;
+        criteria.feedback.as_mut().unwrap().avoid = Some(vec![Keyword {
+            id: 818,
+            name: "based on novel".to_string(),
+        }]);
+        let feedback = candidate_criteria(&criteria).feedback.unwrap();
+
+        assert_eq!((feedback.like, feedback.dislike), (None, None));
+        assert!(feedback.avoid.is_some());
+    }
+}
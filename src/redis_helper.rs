@@ -1,36 +1,43 @@
-use movie_recommendation::*;
-use redis::{Commands, Connection};
-use uuid::Uuid;
+use std::{
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-//const CONNECTION_STRING: &str = "redis://localhost:6379";
+use movie_recommendation::{config::DEFAULT_REDIS_URL, *};
+use redis::{Commands, Connection, RedisResult};
+use uuid::Uuid;
 
-lazy_static! {
-    static ref CONNECTION_STRING: String = match std::env::var("REDIS_CONNECTION_STRING") {
-        Ok(s) => {
-            println!("Env connection string found...");
-            s
-        }
-        Err(e) => {
-            println!("Connection string not set. Defaulting...");
-            "redis://localhost:6379".to_string()
-        }
-    };
+use crate::tmdb_helper::SessionError;
+
+/* Only this many of the most recently seen titles are remembered per media type, so discover */
+/* doesn't have to page past a session's entire history to fill a batch */
+pub const MAX_SEEN_TITLES: isize = 200;
+
+/* Set from config at startup - anything that runs without calling init (tests) falls back to the environment */
+static CONNECTION_STRING: OnceLock<String> = OnceLock::new();
+
+/* Points every session call at the configured redis - only the first call has any effect */
+pub fn init(connection_string: &str) {
+    if CONNECTION_STRING
+        .set(connection_string.to_string())
+        .is_err()
+    {
+        println!(
+            "Redis connection string already set, ignoring {}",
+            connection_string
+        );
+    }
 }
 
 pub async fn criteria_from_cache(
     session_id: &String,
-) -> Result<RecommendationCriteria, redis::RedisError> {
-    match get_connection() {
-        Ok(mut con) => {
-            let redis_result: String = con.get(session_id).expect("Error reading from redis");
+) -> Result<RecommendationCriteria, SessionError> {
+    let mut con = get_connection()?;
 
-            let criteria: RecommendationCriteria =
-                serde_json::from_str(&redis_result).expect("Error parsing result");
+    let stored: Option<String> = con.get(session_id)?;
+    let stored = stored.ok_or_else(|| SessionError::NotFound(session_id.clone()))?;
 
-            Ok(criteria)
-        }
-        Err(err) => Err(err),
-    }
+    serde_json::from_str(&stored).map_err(SessionError::Invalid)
 }
 
 pub async fn criteria_to_cache(
@@ -40,9 +47,7 @@ pub async fn criteria_to_cache(
     match get_connection() {
         Ok(mut con) => {
             let json_string = serde_json::to_string(&criteria).expect("Unable to parse criteria");
-            Ok(con
-                .set(session_id, json_string)
-                .expect("Error writing to redis cache"))
+            con.set(session_id, json_string)
         }
         Err(err) => Err(err),
     }
@@ -53,17 +58,11 @@ pub async fn start_recommendation_session() -> Result<String, redis::RedisError>
         Ok(mut con) => {
             let session_id = Uuid::new_v4().to_string();
 
-            let criteria = RecommendationCriteria {
-                genres: None,
-                watch_providers: None,
-                runtime: None,
-                decade: None,
-                feedback: None,
-            };
+            let criteria = RecommendationCriteria::default();
 
             let json_string = serde_json::to_string(&criteria).expect("Unable to parse criteria");
 
-            let _: () = con.set(&session_id, json_string).unwrap();
+            let _: () = con.set(&session_id, json_string)?;
 
             Ok(session_id)
         }
@@ -71,8 +70,56 @@ pub async fn start_recommendation_session() -> Result<String, redis::RedisError>
     }
 }
 
+/* Seen titles live in a sorted set per session and media type, scored by when they were seen */
+fn seen_key(session_id: &str, media_type: MediaType) -> String {
+    format!("{}:seen:{}", session_id, media_type.as_str())
+}
+
+/* Adds to the session's seen titles without touching its criteria, so it can't overwrite votes */
+/* or picks saved at the same time - the oldest are dropped past MAX_SEEN_TITLES */
+pub async fn mark_seen(session_id: &str, media_type: MediaType, ids: &[i64]) -> RedisResult<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut con = get_connection()?;
+    let key = seen_key(session_id, media_type);
+    let seen_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default();
+    let members: Vec<(u64, i64)> = ids.iter().map(|id| (seen_at, *id)).collect();
+
+    redis::pipe()
+        .atomic()
+        .zadd_multiple(&key, &members)
+        .ignore()
+        .zremrangebyrank(&key, 0, -MAX_SEEN_TITLES - 1)
+        .ignore()
+        .query(&mut con)
+}
+
+pub async fn seen_from_cache(session_id: &str) -> RedisResult<SeenTitles> {
+    let mut con = get_connection()?;
+    let (movies, tv): (Vec<i64>, Vec<i64>) = redis::pipe()
+        .zrange(seen_key(session_id, MediaType::Movie), 0, -1)
+        .zrange(seen_key(session_id, MediaType::Tv), 0, -1)
+        .query(&mut con)?;
+
+    Ok(SeenTitles {
+        movies: movies.into_iter().collect(),
+        tv: tv.into_iter().collect(),
+    })
+}
+
+pub fn connection_string() -> &'static str {
+    CONNECTION_STRING.get_or_init(|| {
+        std::env::var("REDIS_CONNECTION_STRING").unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string())
+    })
+}
+
 fn get_connection() -> Result<Connection, redis::RedisError> {
-    match redis::Client::open(CONNECTION_STRING.as_str()) {
+    match redis::Client::open(connection_string()) {
         Ok(client) => client.get_connection(),
         Err(err) => Err(err),
     }
@@ -81,12 +128,17 @@ fn get_connection() -> Result<Connection, redis::RedisError> {
 pub async fn end_session(session_id: String) {
     let mut con = get_connection().expect("Error connecting to redis");
 
-    let _: () = con.del(session_id).unwrap();
+    let keys = [
+        seen_key(&session_id, MediaType::Movie),
+        seen_key(&session_id, MediaType::Tv),
+        session_id,
+    ];
+    let _: () = con.del(&keys).unwrap();
 }
 
 #[cfg(test)]
 mod local_redis {
-    use std::vec;
+    use std::{collections::HashSet, vec};
 
     use super::*;
     use redis::ConnectionLike;
@@ -112,12 +164,32 @@ mod local_redis {
             }]),
             watch_providers: Some(vec![WatchProvider {
                 logo_path: "/".to_string(),
+                logo_url: None,
                 provider_id: 1,
                 provider_name: "bar".to_string(),
             }]),
             runtime: Some(Runtime::MovieNight),
             decade: Some(Decade::Eighties),
             feedback: None,
+            region: Some("CA".to_string()),
+            monetization_types: Some(vec![MonetizationType::Flatrate, MonetizationType::Rent]),
+            with_cast: Some(vec![Person {
+                id: 1373737,
+                name: "Florence Pugh".to_string(),
+                known_for_department: Some("Acting".to_string()),
+                profile_path: None,
+            }]),
+            with_crew: None,
+            media_type: Some(MediaType::Tv),
+            language: Some("fr-CA".to_string()),
+            quality: Some(Quality::HiddenGems),
+            vote_bounds: Some(VoteBounds {
+                min_vote_average: Some(6.5),
+                ..VoteBounds::default()
+            }),
+            sort: Some(SortBy::ReleaseDate),
+            seen: None,
+            diversity: Some(0.5),
         };
 
         let to_cache_result = criteria_to_cache(&session_id, criteria_start.clone()).await;
@@ -133,10 +205,52 @@ mod local_redis {
         end_session(session_id).await;
     }
 
+    #[tokio::test]
+    async fn redis_unknown_session() {
+        let session_id = Uuid::new_v4().to_string();
+
+        assert!(matches!(
+            criteria_from_cache(&session_id).await,
+            Err(SessionError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn redis_seen_titles() {
+        let session_id = start_recommendation_session().await.unwrap();
+
+        mark_seen(&session_id, MediaType::Tv, &[66732])
+            .await
+            .unwrap();
+        mark_seen(&session_id, MediaType::Movie, &[1, 2])
+            .await
+            .unwrap();
+        // Seen titles are kept apart from the criteria, so saving those doesn't lose them
+        criteria_to_cache(&session_id, RecommendationCriteria::default())
+            .await
+            .unwrap();
+
+        let seen = seen_from_cache(&session_id).await.unwrap();
+        assert_eq!(seen.movies, HashSet::from([1, 2]));
+        assert_eq!(seen.tv, HashSet::from([66732]));
+
+        // Only the most recent are remembered
+        let later: Vec<i64> = (3..3 + MAX_SEEN_TITLES as i64).collect();
+        mark_seen(&session_id, MediaType::Movie, &later)
+            .await
+            .unwrap();
+
+        let seen = seen_from_cache(&session_id).await.unwrap();
+        assert_eq!(seen.movies, later.into_iter().collect());
+        assert_eq!(seen.tv, HashSet::from([66732]));
+
+        end_session(session_id).await;
+    }
+
     #[tokio::test]
     async fn redis_start_session() {
         let empty_criteria_string =
-            "{\"genres\":null,\"watch_providers\":null,\"runtime\":null,\"decade\":null,\"feedback\":null}";
+            "{\"genres\":null,\"watch_providers\":null,\"runtime\":null,\"decade\":null,\"feedback\":null,\"region\":null,\"monetization_types\":null,\"with_cast\":null,\"with_crew\":null,\"media_type\":null,\"language\":null,\"quality\":null,\"vote_bounds\":null,\"sort\":null,\"diversity\":null}";
         let response = start_recommendation_session().await;
 
         assert!(response.is_ok());
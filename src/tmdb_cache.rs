@@ -0,0 +1,375 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::Serialize;
+
+/* Prefix for cache entries stored in redis, keeps them apart from session criteria */
+const REDIS_KEY_PREFIX: &str = "tmdb_cache:";
+
+/* Longest a cache lookup or write waits on redis before carrying on without it */
+pub const REDIS_TIMEOUT: Duration = Duration::from_millis(250);
+
+/* Kinds of TMDB lookups worth caching - each one gets its own TTL and hit/miss counters */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKind {
+    Genres,
+    ProviderList,
+    Keywords,
+    MovieProviders,
+    Configuration,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 5] = [
+        CacheKind::Genres,
+        CacheKind::ProviderList,
+        CacheKind::Keywords,
+        CacheKind::MovieProviders,
+        CacheKind::Configuration,
+    ];
+
+    fn index(&self) -> usize {
+        match self {
+            CacheKind::Genres => 0,
+            CacheKind::ProviderList => 1,
+            CacheKind::Keywords => 2,
+            CacheKind::MovieProviders => 3,
+            CacheKind::Configuration => 4,
+        }
+    }
+}
+
+/* How long each kind of lookup stays cached */
+#[derive(Debug, Clone)]
+pub struct CacheTtls {
+    pub genres: Duration,
+    pub provider_list: Duration,
+    pub keywords: Duration,
+    /* Streaming availability moves around, so this is kept much shorter than the catalog lists */
+    pub movie_providers: Duration,
+    /* Image hosts and sizes - TMDB suggests refreshing these every few days */
+    pub configuration: Duration,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            genres: Duration::from_secs(24 * 60 * 60),
+            provider_list: Duration::from_secs(24 * 60 * 60),
+            keywords: Duration::from_secs(7 * 24 * 60 * 60),
+            movie_providers: Duration::from_secs(6 * 60 * 60),
+            configuration: Duration::from_secs(3 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl CacheTtls {
+    pub fn ttl(&self, kind: CacheKind) -> Duration {
+        match kind {
+            CacheKind::Genres => self.genres,
+            CacheKind::ProviderList => self.provider_list,
+            CacheKind::Keywords => self.keywords,
+            CacheKind::MovieProviders => self.movie_providers,
+            CacheKind::Configuration => self.configuration,
+        }
+    }
+}
+
+struct CacheEntry {
+    body: String,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct CacheKindStats {
+    pub kind: CacheKind,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub redis: bool,
+    pub kinds: Vec<CacheKindStats>,
+}
+
+/* In-process LRU of raw TMDB response bodies keyed by request URL, optionally backed by redis */
+/* so cached lookups survive restarts and are shared between instances */
+pub struct TmdbCache {
+    ttls: CacheTtls,
+    entries: Mutex<LruCache<String, CacheEntry>>,
+    /* Shared by every request and reconnects on its own, so a lookup never opens a connection */
+    redis: Option<ConnectionManager>,
+    counters: [CacheCounters; 5],
+}
+
+impl TmdbCache {
+    pub fn new(capacity: usize, ttls: CacheTtls) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            ttls,
+            entries: Mutex::new(LruCache::new(capacity)),
+            redis: None,
+            counters: Default::default(),
+        }
+    }
+
+    pub fn with_redis(mut self, connection: ConnectionManager) -> Self {
+        self.redis = Some(connection);
+        self
+    }
+
+    /* Connects to redis for with_redis, giving up after REDIS_TIMEOUT */
+    pub async fn connect_redis(client: redis::Client) -> redis::RedisResult<ConnectionManager> {
+        match tokio::time::timeout(REDIS_TIMEOUT, ConnectionManager::new(client)).await {
+            Ok(result) => result,
+            Err(_) => Err(Self::timed_out()),
+        }
+    }
+
+    /* Looks up a cached body, falling back to redis when it isn't held in process */
+    pub async fn get(&self, kind: CacheKind, key: &str) -> Option<String> {
+        let body = match self.get_local(key) {
+            Some(body) => Some(body),
+            None => self.get_redis(key).await,
+        };
+
+        let counters = &self.counters[kind.index()];
+        match body {
+            Some(_) => counters.hits.fetch_add(1, Ordering::Relaxed),
+            None => counters.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        body
+    }
+
+    pub async fn insert(&self, kind: CacheKind, key: &str, body: String) {
+        let ttl = self.ttls.ttl(kind);
+
+        // Redis rejects an expiry of 0, and an entry that short isn't worth sharing anyway
+        if let (Some(connection), true) = (&self.redis, ttl.as_secs() > 0) {
+            let mut con = connection.clone();
+            let write = con.set_ex::<_, _, ()>(Self::redis_key(key), &body, ttl.as_secs());
+
+            let result = match tokio::time::timeout(REDIS_TIMEOUT, write).await {
+                Ok(result) => result,
+                Err(_) => Err(Self::timed_out()),
+            };
+
+            if let Err(err) = result {
+                println!("Error writing {} to redis cache: {}", key, err);
+            }
+        }
+
+        self.insert_local(key, body, ttl);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.lock_entries().len(),
+            redis: self.redis.is_some(),
+            kinds: CacheKind::ALL
+                .iter()
+                .map(|kind| {
+                    let counters = &self.counters[kind.index()];
+                    CacheKindStats {
+                        kind: *kind,
+                        hits: counters.hits.load(Ordering::Relaxed),
+                        misses: counters.misses.load(Ordering::Relaxed),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn get_local(&self, key: &str) -> Option<String> {
+        let mut entries = self.lock_entries();
+
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.body.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert_local(&self, key: &str, body: String, ttl: Duration) {
+        self.lock_entries().put(
+            key.to_string(),
+            CacheEntry {
+                body,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /* A redis hit is copied into the LRU for whatever time redis has left on it */
+    async fn get_redis(&self, key: &str) -> Option<String> {
+        let mut con = self.redis.as_ref()?.clone();
+
+        let mut pipe = redis::pipe();
+        pipe.get(Self::redis_key(key)).ttl(Self::redis_key(key));
+
+        let result: Result<(Option<String>, i64), redis::RedisError> =
+            match tokio::time::timeout(REDIS_TIMEOUT, pipe.query_async(&mut con)).await {
+                Ok(result) => result,
+                Err(_) => Err(Self::timed_out()),
+            };
+
+        match result {
+            Ok((Some(body), seconds)) if seconds > 0 => {
+                self.insert_local(key, body.clone(), Duration::from_secs(seconds as u64));
+                Some(body)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                println!("Error reading {} from redis cache: {}", key, err);
+                None
+            }
+        }
+    }
+
+    fn lock_entries(&self) -> MutexGuard<'_, LruCache<String, CacheEntry>> {
+        // Entries are plain data, a panic elsewhere while holding the lock can't leave them half written
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn redis_key(key: &str) -> String {
+        format!("{}{}", REDIS_KEY_PREFIX, key)
+    }
+
+    fn timed_out() -> redis::RedisError {
+        redis::RedisError::from((redis::ErrorKind::IoError, "Timed out waiting for redis"))
+    }
+}
+
+/* ======================================================================================================================== */
+/* ====================================================== UNIT TESTS ====================================================== */
+/* ======================================================================================================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_stats(cache: &TmdbCache, kind: CacheKind) -> (u64, u64) {
+        let stats = cache.stats();
+        let kind_stats = stats.kinds.iter().find(|k| k.kind == kind).unwrap();
+        (kind_stats.hits, kind_stats.misses)
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_misses() {
+        let cache = TmdbCache::new(10, CacheTtls::default());
+
+        assert!(cache
+            .get(CacheKind::Genres, "genre/movie/list")
+            .await
+            .is_none());
+
+        cache
+            .insert(
+                CacheKind::Genres,
+                "genre/movie/list",
+                String::from("{\"genres\":[]}"),
+            )
+            .await;
+
+        assert_eq!(
+            cache.get(CacheKind::Genres, "genre/movie/list").await,
+            Some(String::from("{\"genres\":[]}"))
+        );
+        assert!(cache
+            .get(CacheKind::Keywords, "movie/1/keywords")
+            .await
+            .is_none());
+
+        assert_eq!(kind_stats(&cache, CacheKind::Genres), (1, 1));
+        assert_eq!(kind_stats(&cache, CacheKind::Keywords), (0, 1));
+        assert_eq!(kind_stats(&cache, CacheKind::ProviderList), (0, 0));
+        assert_eq!(cache.stats().entries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_expiry() {
+        let ttls = CacheTtls {
+            keywords: Duration::ZERO,
+            ..CacheTtls::default()
+        };
+        let cache = TmdbCache::new(10, ttls);
+
+        cache
+            .insert(CacheKind::Keywords, "movie/1/keywords", String::from("{}"))
+            .await;
+        cache
+            .insert(CacheKind::Genres, "genre/movie/list", String::from("{}"))
+            .await;
+
+        assert!(cache
+            .get(CacheKind::Keywords, "movie/1/keywords")
+            .await
+            .is_none());
+        assert!(cache
+            .get(CacheKind::Genres, "genre/movie/list")
+            .await
+            .is_some());
+
+        // Expired entries are dropped once they're looked up
+        assert_eq!(cache.stats().entries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_eviction() {
+        let cache = TmdbCache::new(2, CacheTtls::default());
+
+        cache
+            .insert(CacheKind::Keywords, "movie/1/keywords", String::from("1"))
+            .await;
+        cache
+            .insert(CacheKind::Keywords, "movie/2/keywords", String::from("2"))
+            .await;
+
+        // Touch 1 so 2 is the least recently used
+        assert!(cache
+            .get(CacheKind::Keywords, "movie/1/keywords")
+            .await
+            .is_some());
+
+        cache
+            .insert(CacheKind::Keywords, "movie/3/keywords", String::from("3"))
+            .await;
+
+        assert!(cache
+            .get(CacheKind::Keywords, "movie/1/keywords")
+            .await
+            .is_some());
+        assert!(cache
+            .get(CacheKind::Keywords, "movie/2/keywords")
+            .await
+            .is_none());
+        assert!(cache
+            .get(CacheKind::Keywords, "movie/3/keywords")
+            .await
+            .is_some());
+    }
+}
@@ -1,87 +1,568 @@
 #![allow(dead_code, unused_variables)]
 use movie_recommendation::*;
-use std::{collections::HashMap, sync::Arc};
+use rand::seq::SliceRandom;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
 
 use crate::redis_helper;
 
-const NUM_RESULTS: u8 = 5;
+/* Number of unique movies returned per recommendation batch */
+const NUM_RECOMMENDATIONS: usize = 11;
+
+/* How many of the most popular results a random sort picks from */
+const RANDOM_POOL_SIZE: usize = 60;
+
+/* How many of the most popular results are scored locally when sorting by relevance */
+const CANDIDATE_POOL_SIZE: usize = 40;
+
+/* Errors from building recommendations for a session - criteria come from redis, movies from TMDB */
+#[derive(Debug)]
+pub enum SessionError {
+    Redis(redis::RedisError),
+    /* No criteria stored under the session ID - it never existed or has ended */
+    NotFound(String),
+    /* Stored criteria that don't parse, like ones written before a schema change */
+    Invalid(serde_json::Error),
+    /* The session hasn't picked everything recommendations need yet - names the first missing pick */
+    Incomplete(&'static str),
+    Tmdb(TmdbError),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Redis(err) => write!(f, "Error fetching session: {}", err),
+            SessionError::NotFound(session_id) => write!(f, "No session {}", session_id),
+            SessionError::Invalid(err) => write!(f, "Error reading session: {}", err),
+            SessionError::Incomplete(pick) => write!(f, "No {} picked for session", pick),
+            SessionError::Tmdb(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
 
+impl From<redis::RedisError> for SessionError {
+    fn from(err: redis::RedisError) -> Self {
+        SessionError::Redis(err)
+    }
+}
+
+impl From<TmdbError> for SessionError {
+    fn from(err: TmdbError) -> Self {
+        SessionError::Tmdb(err)
+    }
+}
+
+/* Recommendations come back as whichever media type the session picked */
+pub enum SessionRecommendations {
+    Movies(Vec<AsyncRecommendation>),
+    Tv(Vec<AsyncTvRecommendation>),
+}
+
+/* fallback_language is only used when the session hasn't picked a language of its own */
+/* sort_override applies to this batch only - the session keeps its own sort */
+/* Nothing is marked seen here - the caller does that once the batch has actually been sent */
+/* Also returns the criteria the batch was picked with, for explaining it */
 pub async fn get_recommendations_for_session(
-    tmdb: Arc<Tmdb>,
+    catalog: Arc<dyn MovieCatalog>,
     session_id: String,
-) -> Result<Vec<AsyncRecommendation>, Box<dyn std::error::Error>> {
-    let criteria = redis_helper::criteria_from_cache(&session_id).await?;
-
-    let recommendations = tmdb
-        .get_recommendations(
-            criteria.genres.expect("No genres for ID"),
-            criteria.watch_providers.expect("No watch providers for ID"),
-            criteria.runtime.expect("No runtime for ID"),
-            criteria.decade.expect("No decade for ID"),
-            criteria.feedback,
-        )
+    fallback_language: Option<String>,
+    sort_override: Option<SortBy>,
+) -> Result<(RecommendationCriteria, SessionRecommendations), SessionError> {
+    let mut criteria = redis_helper::criteria_from_cache(&session_id).await?;
+    criteria.seen = Some(redis_helper::seen_from_cache(&session_id).await?);
+
+    if criteria.language.is_none() {
+        criteria.language = fallback_language;
+    }
+
+    if sort_override.is_some() {
+        criteria.sort = sort_override;
+    }
+
+    // Discover works without these, but a session isn't ready until every one has been picked
+    let picks = [
+        ("genres", criteria.genres.is_some()),
+        ("watch providers", criteria.watch_providers.is_some()),
+        ("runtime", criteria.runtime.is_some()),
+        ("decade", criteria.decade.is_some()),
+    ];
+    if let Some((pick, _)) = picks.iter().find(|(_, picked)| !picked) {
+        return Err(SessionError::Incomplete(pick));
+    }
+
+    let recommendations = match criteria.media_type() {
+        MediaType::Movie => {
+            SessionRecommendations::Movies(get_movie_recommendations(catalog, &criteria).await?)
+        }
+        MediaType::Tv => {
+            SessionRecommendations::Tv(get_tv_recommendations(catalog, &criteria).await?)
+        }
+    };
+
+    Ok((criteria, recommendations))
+}
+
+/* Spawns a provider lookup for the given ID, trimmed down to what the session can watch */
+fn spawn_provider_lookup(
+    catalog: &Arc<dyn MovieCatalog>,
+    media_type: MediaType,
+    id: i64,
+    criteria: &RecommendationCriteria,
+) -> tokio::task::JoinHandle<Result<WatchProviderRegion, TmdbError>> {
+    let temp_catalog = Arc::clone(catalog);
+    let id = id.to_string();
+    let region = criteria.region().to_string();
+    let monetization_types = criteria.monetization_types();
+
+    tokio::spawn(async move {
+        let providers = match media_type {
+            MediaType::Movie => temp_catalog.get_watch_providers_by_id(&id).await,
+            MediaType::Tv => temp_catalog.get_tv_watch_providers_by_id(&id).await,
+        };
+
+        providers.map(|providers| providers.providers_for_region(&region, &monetization_types))
+    })
+}
+
+/* Spawns a lookup for the movie's best trailer in the session's language */
+fn spawn_trailer_lookup(
+    catalog: &Arc<dyn MovieCatalog>,
+    movie_id: i64,
+    criteria: &RecommendationCriteria,
+) -> tokio::task::JoinHandle<Result<Option<Trailer>, TmdbError>> {
+    let temp_catalog = Arc::clone(catalog);
+    let language = criteria.language().to_string();
+
+    tokio::spawn(async move {
+        temp_catalog
+            .get_videos_for_id(&movie_id, &language)
+            .await
+            .map(|videos| videos.best_trailer(&language))
+    })
+}
+
+/* Spawns a lookup for the movie's keywords, which explanations pick liked ones out of */
+fn spawn_keyword_lookup(
+    catalog: &Arc<dyn MovieCatalog>,
+    movie_id: i64,
+) -> tokio::task::JoinHandle<Result<KeywordResponse, TmdbError>> {
+    let temp_catalog = Arc::clone(catalog);
+
+    tokio::spawn(async move { temp_catalog.get_keywords_for_id(&movie_id).await })
+}
+
+/* Whether the batch is scored locally - see ranking */
+fn ranks_locally(criteria: &RecommendationCriteria) -> bool {
+    criteria.sort() == SortBy::Relevance
+        && !ranking::Preferences::from_criteria(criteria).is_empty()
+}
+
+/* Whether the batch is spread out across franchises and subgenres - explicit sorts keep TMDB's order */
+fn diversifies(criteria: &RecommendationCriteria) -> bool {
+    criteria.sort() == SortBy::Relevance && criteria.diversity() > 0.0
+}
+
+/* Random and relevance sorts fetch a bigger pool of popular results to pick from */
+fn batch_size(criteria: &RecommendationCriteria) -> usize {
+    match criteria.sort() {
+        SortBy::Random => RANDOM_POOL_SIZE,
+        _ if ranks_locally(criteria) || diversifies(criteria) => CANDIDATE_POOL_SIZE,
+        _ => NUM_RECOMMENDATIONS,
+    }
+}
+
+/* Criteria discover is asked with - learned keywords are left to the ranking when it runs */
+fn discover_criteria(criteria: &RecommendationCriteria) -> RecommendationCriteria {
+    match ranks_locally(criteria) {
+        true => ranking::candidate_criteria(criteria),
+        false => criteria.clone(),
+    }
+}
+
+/* Trims discover results down to one batch, shuffling, re-ranking or diversifying them first depending on the sort */
+/* Also hands back the keywords looked up for ranking by ID, so they don't need fetching again - empty if it didn't rank */
+async fn pick_batch<T>(
+    catalog: &Arc<dyn MovieCatalog>,
+    media_type: MediaType,
+    criteria: &RecommendationCriteria,
+    mut results: Vec<T>,
+    id_of: fn(&T) -> i64,
+    genres_of: fn(&T) -> &[i32],
+) -> (Vec<T>, HashMap<i64, Vec<Keyword>>) {
+    if criteria.sort() == SortBy::Random {
+        results.shuffle(&mut rand::thread_rng());
+    }
+
+    if !ranks_locally(criteria) && !diversifies(criteria) {
+        results.truncate(NUM_RECOMMENDATIONS);
+        return (results, HashMap::new());
+    }
+
+    let ids: Vec<i64> = results.iter().map(id_of).collect();
+    let keyword_futures = get_keyword_futures(catalog, media_type, ids.clone()).await;
+
+    // Keyword lookups are already running, so these go alongside them
+    let collection_ids = match (diversifies(criteria), media_type) {
+        (true, MediaType::Movie) => get_collection_ids(catalog, &ids, criteria.language()).await,
+        _ => HashMap::new(),
+    };
+
+    let mut keywords: HashMap<i64, Vec<Keyword>> = HashMap::new();
+
+    // Candidates without keywords can still score on genres and popularity
+    for feedback in keyword_futures {
+        match feedback.keyword_future.await {
+            Ok(Ok(response)) => {
+                keywords.insert(feedback.movie_id, response.keywords);
+            }
+            Ok(Err(err)) => println!("Error fetching keywords for {}: {}", feedback.movie_id, err),
+            Err(err) => println!("{}", err),
+        }
+    }
+
+    let candidates = results
+        .into_iter()
+        .map(|item| ranking::Candidate {
+            keyword_ids: keywords
+                .get(&id_of(&item))
+                .map(|keywords| keywords.iter().map(|k| k.id).collect())
+                .unwrap_or_default(),
+            genre_ids: genres_of(&item).to_vec(),
+            collection_id: collection_ids.get(&id_of(&item)).copied(),
+            item,
+        })
+        .collect();
+
+    let diversity = match diversifies(criteria) {
+        true => criteria.diversity(),
+        false => 0.0,
+    };
+
+    let batch = ranking::rank(
+        candidates,
+        &ranking::Preferences::from_criteria(criteria),
+        &ranking::RankingWeights::default(),
+        diversity,
+        NUM_RECOMMENDATIONS,
+    );
+
+    (batch, keywords)
+}
+
+/* Collection each movie belongs to - discover results don't say, so it takes each movie's details */
+/* Movies whose details can't be fetched are treated as standalone */
+async fn get_collection_ids(
+    catalog: &Arc<dyn MovieCatalog>,
+    movie_ids: &[i64],
+    language: &str,
+) -> HashMap<i64, i64> {
+    let lookups: Vec<_> = movie_ids
+        .iter()
+        .map(|&id| {
+            let temp_catalog = Arc::clone(catalog);
+            let language = language.to_string();
+            let handle =
+                tokio::spawn(async move { temp_catalog.get_movie_details(&id, &language).await });
+            (id, handle)
+        })
+        .collect();
+
+    let mut collection_ids = HashMap::new();
+
+    for (id, handle) in lookups {
+        let collection = await_lookup(handle, "details", id)
+            .await
+            .and_then(|details| details.belongs_to_collection);
+
+        if let Some(collection) = collection {
+            collection_ids.insert(id, collection.id);
+        }
+    }
+
+    collection_ids
+}
+
+async fn get_movie_recommendations(
+    catalog: Arc<dyn MovieCatalog>,
+    criteria: &RecommendationCriteria,
+) -> Result<Vec<AsyncRecommendation>, TmdbError> {
+    let recommendations = catalog
+        .get_recommendation_pages(&discover_criteria(criteria), 1, batch_size(criteria))
         .await?;
-    let mut index = 0;
+    let (movies, mut keywords) = pick_batch(
+        &catalog,
+        MediaType::Movie,
+        criteria,
+        recommendations.results,
+        |movie| movie.id,
+        |movie| &movie.genre_ids,
+    )
+    .await;
 
     let mut movie_recommendations = vec![];
 
-    for movie in recommendations.results {
-        if index > 10 {
-            break;
-        }
-        let temp_tmdb = Arc::clone(&tmdb);
-        let handle = tokio::spawn(async move {
-            let movie_id = movie.id.to_string();
-            temp_tmdb
-                .get_watch_providers_by_id(&movie_id)
-                .await
-                .expect("Unable to call tmdb")
-        });
+    for movie in movies {
+        let handle = spawn_provider_lookup(&catalog, MediaType::Movie, movie.id, criteria);
+        let trailer_handle = spawn_trailer_lookup(&catalog, movie.id, criteria);
+        let keywords = match keywords.remove(&movie.id) {
+            Some(keywords) => AsyncKeywords::Loaded(keywords),
+            None => AsyncKeywords::Pending(spawn_keyword_lookup(&catalog, movie.id)),
+        };
         movie_recommendations.push(AsyncRecommendation {
             movie,
             async_providers: handle,
+            async_trailer: trailer_handle,
+            keywords,
         });
-        index += 1;
     }
 
     Ok(movie_recommendations)
 }
 
+async fn get_tv_recommendations(
+    catalog: Arc<dyn MovieCatalog>,
+    criteria: &RecommendationCriteria,
+) -> Result<Vec<AsyncTvRecommendation>, TmdbError> {
+    let recommendations = catalog
+        .get_tv_recommendation_pages(&discover_criteria(criteria), 1, batch_size(criteria))
+        .await?;
+    let (shows, _) = pick_batch(
+        &catalog,
+        MediaType::Tv,
+        criteria,
+        recommendations.results,
+        |show| show.id,
+        |show| &show.genre_ids,
+    )
+    .await;
+
+    let mut tv_recommendations = vec![];
+
+    for show in shows {
+        let handle = spawn_provider_lookup(&catalog, MediaType::Tv, show.id, criteria);
+        tv_recommendations.push(AsyncTvRecommendation {
+            show,
+            async_providers: handle,
+        });
+    }
+
+    Ok(tv_recommendations)
+}
+
+/* Movies related to movie_id that the session could actually watch - uses the session's language when it has one */
+pub async fn get_more_like_this_for_session(
+    catalog: Arc<dyn MovieCatalog>,
+    movie_id: i64,
+    session_id: String,
+    fallback_language: Option<String>,
+) -> Result<Vec<MovieRecommendation>, SessionError> {
+    let mut criteria = redis_helper::criteria_from_cache(&session_id).await?;
+
+    if criteria.language.is_none() {
+        criteria.language = fallback_language;
+    }
+
+    Ok(get_more_like_this(catalog, movie_id, &criteria).await?)
+}
+
+/* Lookups for a single candidate that fail are logged and come back empty, rather than failing the whole list */
+pub(crate) async fn await_lookup<T>(
+    handle: tokio::task::JoinHandle<Result<T, TmdbError>>,
+    what: &str,
+    id: i64,
+) -> Option<T> {
+    match handle.await {
+        Ok(Ok(result)) => Some(result),
+        Ok(Err(err)) => {
+            println!("Error fetching {} for {}: {}", what, id, err);
+            None
+        }
+        Err(err) => {
+            println!("{}", err);
+            None
+        }
+    }
+}
+
+/* Keywords only go towards the explanation, so failing to get them leaves it without any */
+pub(crate) async fn await_keywords(keywords: AsyncKeywords, id: i64) -> Vec<Keyword> {
+    match keywords {
+        AsyncKeywords::Loaded(keywords) => keywords,
+        AsyncKeywords::Pending(handle) => await_lookup(handle, "keywords", id)
+            .await
+            .map(|response| response.keywords)
+            .unwrap_or_default(),
+    }
+}
+
+/* Merges TMDB's recommendations and similar movies, keeping those that fit the session's providers, runtime and decade */
+/* Criteria that haven't been picked yet aren't filtered on */
+async fn get_more_like_this(
+    catalog: Arc<dyn MovieCatalog>,
+    movie_id: i64,
+    criteria: &RecommendationCriteria,
+) -> Result<Vec<MovieRecommendation>, TmdbError> {
+    let language = criteria.language().to_string();
+
+    let (recommended, similar) = tokio::join!(
+        catalog.get_recommendations_for_movie(&movie_id, 1, &language),
+        catalog.get_similar_movies(&movie_id, 1, &language)
+    );
+
+    // Recommendations go first - they're based on what viewers actually watched next
+    let mut seen = HashSet::from([movie_id]);
+    let candidates: Vec<Movie> = recommended?
+        .results
+        .into_iter()
+        .chain(similar?.results)
+        .filter(|movie| seen.insert(movie.id))
+        .filter(|movie| match &criteria.decade {
+            Some(decade) => decade.contains_date(&movie.release_date),
+            None => true,
+        })
+        .collect();
+
+    let mut candidates = candidates.into_iter();
+    let mut matches = vec![];
+
+    // Candidates are checked a few at a time, only as many as there are places left, so a list that
+    // fills early doesn't leave lookups running for movies that won't be shown
+    loop {
+        let needed = NUM_RECOMMENDATIONS - matches.len();
+        if needed == 0 {
+            break;
+        }
+
+        // List results don't carry a runtime, so that check needs each movie's details
+        let checks: Vec<_> = candidates
+            .by_ref()
+            .take(needed)
+            .map(|movie| {
+                let providers =
+                    spawn_provider_lookup(&catalog, MediaType::Movie, movie.id, criteria);
+                let details = criteria.runtime.as_ref().map(|_| {
+                    let temp_catalog = Arc::clone(&catalog);
+                    let language = language.clone();
+                    let id = movie.id;
+                    tokio::spawn(
+                        async move { temp_catalog.get_movie_details(&id, &language).await },
+                    )
+                });
+                (movie, providers, details)
+            })
+            .collect();
+
+        if checks.is_empty() {
+            break;
+        }
+
+        for (movie, providers, details) in checks {
+            let providers = match await_lookup(providers, "watch providers", movie.id).await {
+                Some(providers) => providers,
+                None => continue,
+            };
+
+            if let Some(watch_providers) = &criteria.watch_providers {
+                if !providers.offers_any(watch_providers) {
+                    continue;
+                }
+            }
+
+            let minutes = match (&criteria.runtime, details) {
+                (Some(runtime), Some(details)) => {
+                    let minutes = await_lookup(details, "details", movie.id)
+                        .await
+                        .and_then(|details| details.runtime);
+
+                    if !minutes.is_some_and(|minutes| runtime.includes(minutes)) {
+                        continue;
+                    }
+
+                    minutes
+                }
+                _ => None,
+            };
+
+            // Only movies that made the list get a trailer and keywords
+            let trailer = spawn_trailer_lookup(&catalog, movie.id, criteria);
+            let keywords = spawn_keyword_lookup(&catalog, movie.id);
+            matches.push((movie, providers, minutes, trailer, keywords));
+        }
+    }
+
+    let mut recommendations = vec![];
+
+    for (movie, providers, minutes, trailer, keywords) in matches {
+        // Missing trailers and keywords aren't a reason to drop a movie
+        let trailer = await_lookup(trailer, "trailer", movie.id).await.flatten();
+        let keywords = await_lookup(keywords, "keywords", movie.id)
+            .await
+            .map(|response| response.keywords)
+            .unwrap_or_default();
+
+        let explanation = Explanation::new(criteria, &movie, &keywords, &providers, minutes);
+
+        recommendations.push(MovieRecommendation {
+            movie,
+            providers,
+            trailer,
+            explanation,
+        });
+    }
+
+    Ok(recommendations)
+}
+
 pub async fn get_movies_from_title(
     movie_title: String,
-    tmdb: Arc<Tmdb>,
-) -> Result<Vec<Movie>, Box<dyn std::error::Error>> {
-    let search_result = tmdb.search_by_title(&movie_title).await?;
+    catalog: Arc<dyn MovieCatalog>,
+    language: &str,
+) -> Result<Vec<Movie>, TmdbError> {
+    let search_result = catalog
+        .search_by_title(&movie_title, None, 1, language)
+        .await?;
 
     Ok(search_result.results)
 }
 
+/* Where a movie can be watched in the region, keeping only the given monetization types */
 pub async fn get_providers_from_id(
-    tmdb: &Tmdb,
+    catalog: &dyn MovieCatalog,
     movie_id: i64,
-) -> Result<Vec<WatchProvider>, Box<dyn std::error::Error>> {
-    let provider_results = tmdb
+    region: &str,
+    monetization_types: &[MonetizationType],
+) -> Result<WatchProviderRegion, TmdbError> {
+    let provider_results = catalog
         .get_watch_providers_by_id(&movie_id.to_string())
         .await?;
 
-    Ok(provider_results.results.us.flatrate)
+    Ok(provider_results.providers_for_region(region, monetization_types))
 }
 
 struct AsyncFeedback {
     movie_id: i64,
-    keyword_future: tokio::task::JoinHandle<KeywordResponse>,
+    keyword_future: tokio::task::JoinHandle<Result<KeywordResponse, TmdbError>>,
 }
 
-async fn get_keyword_futures(tmdb: &Arc<Tmdb>, id_list: Vec<i64>) -> Vec<AsyncFeedback> {
+async fn get_keyword_futures(
+    catalog: &Arc<dyn MovieCatalog>,
+    media_type: MediaType,
+    id_list: Vec<i64>,
+) -> Vec<AsyncFeedback> {
     let mut futures: Vec<AsyncFeedback> = vec![];
 
     for id in id_list {
-        let temp_tmdb = Arc::clone(&tmdb);
+        let temp_catalog = Arc::clone(catalog);
         let handle = tokio::spawn(async move {
-            temp_tmdb
-                .get_keywords_for_id(&id)
-                .await
-                .expect("Unable to call tmdb")
+            match media_type {
+                MediaType::Movie => temp_catalog.get_keywords_for_id(&id).await,
+                MediaType::Tv => temp_catalog.get_tv_keywords_for_id(&id).await,
+            }
         });
         futures.push(AsyncFeedback {
             movie_id: id,
@@ -96,8 +577,13 @@ async fn get_keyword_list(feedback: Vec<AsyncFeedback>) -> Vec<Keyword> {
     let mut keywords_list: Vec<Keyword> = vec![];
     for keyword_future in feedback {
         let keywords = keyword_future.keyword_future.await;
+        // A movie we can't get keywords for shouldn't sink the rest of the feedback
         match keywords {
-            Ok(mut keyword_response) => keywords_list.append(&mut keyword_response.keywords),
+            Ok(Ok(mut keyword_response)) => keywords_list.append(&mut keyword_response.keywords),
+            Ok(Err(err)) => println!(
+                "Error fetching keywords for {}: {}",
+                keyword_future.movie_id, err
+            ),
             Err(err) => println!("{}", err),
         };
     }
@@ -118,37 +604,34 @@ async fn get_keyword_votes(keywords: Vec<Keyword>) -> HashMap<i64, i16> {
     votes
 }
 
-async fn refine_keywords(
-    mut upvotes: HashMap<i64, i16>,
-    mut downvotes: HashMap<i64, i16>,
-) -> (HashMap<i64, i16>, HashMap<i64, i16>) {
-    let mut remove_upvotes: Vec<i64> = vec![];
-
-    for (id, count) in &mut upvotes {
-        if downvotes.contains_key(&id) {
-            if downvotes.get(&id).unwrap() < count {
-                downvotes.remove(&id);
-            } else {
-                // Can't modify a collection we're iterating over
-                remove_upvotes.push(*id);
-            }
-        }
+/* Net votes per keyword - positive means it turned up more in liked movies than disliked ones */
+fn keyword_weights(
+    upvotes: &HashMap<i64, i16>,
+    downvotes: &HashMap<i64, i16>,
+) -> HashMap<i64, f64> {
+    let mut weights: HashMap<i64, f64> = HashMap::new();
+
+    for (id, count) in upvotes {
+        *weights.entry(*id).or_default() += f64::from(*count);
     }
 
-    for id in remove_upvotes {
-        upvotes.remove(&id);
+    for (id, count) in downvotes {
+        *weights.entry(*id).or_default() -= f64::from(*count);
     }
 
-    (upvotes, downvotes)
+    weights
 }
 
+/* IDs are movie or show IDs depending on media_type - TMDB numbers them separately */
+/* Returns every keyword's net votes, ready to be merged into the session's KeywordProfile */
 pub async fn process_feedback(
-    tmdb: Arc<Tmdb>,
+    catalog: Arc<dyn MovieCatalog>,
+    media_type: MediaType,
     thumbs_up_ids: Vec<i64>,
     thumbs_down_ids: Vec<i64>,
-) -> (Vec<i64>, Vec<i64>) {
-    let thumbs_up_future = get_keyword_futures(&tmdb, thumbs_up_ids);
-    let thumbs_down_future = get_keyword_futures(&tmdb, thumbs_down_ids);
+) -> HashMap<i64, f64> {
+    let thumbs_up_future = get_keyword_futures(&catalog, media_type, thumbs_up_ids);
+    let thumbs_down_future = get_keyword_futures(&catalog, media_type, thumbs_down_ids);
 
     let thumbs_up_keywords = get_keyword_list(thumbs_up_future.await);
     let thumbs_down_keywords = get_keyword_list(thumbs_down_future.await);
@@ -156,31 +639,7 @@ pub async fn process_feedback(
     let thumbs_up_votes = get_keyword_votes(thumbs_up_keywords.await).await;
     let thumbs_down_votes = get_keyword_votes(thumbs_down_keywords.await).await;
 
-    let (refined_upvotes, refined_downvotes) =
-        refine_keywords(thumbs_up_votes, thumbs_down_votes).await;
-
-    let mut sorted_up_votes: Vec<_> = refined_upvotes.iter().collect();
-
-    sorted_up_votes.sort_by_key(|&(_, count)| std::cmp::Reverse(*count));
-
-    let mut sorted_down_votes: Vec<_> = refined_downvotes.iter().collect();
-
-    sorted_down_votes.sort_by_key(|&(_, count)| std::cmp::Reverse(*count));
-
-    let mut criteria_upvotes: Vec<i64> = vec![];
-    let mut criteria_downvotes: Vec<i64> = vec![];
-
-    println!("Upvotes: ");
-    for (&id, &count) in sorted_up_votes.iter().take(5) {
-        criteria_upvotes.push(id);
-    }
-
-    println!("Downvotes: ");
-    for (&id, &count) in sorted_down_votes.iter().take(5) {
-        criteria_downvotes.push(id)
-    }
-
-    (criteria_upvotes, criteria_downvotes)
+    keyword_weights(&thumbs_up_votes, &thumbs_down_votes)
 }
 
 /* ======================================================================================================================== */
@@ -213,12 +672,24 @@ mod tests {
             ]),
             watch_providers: Some(vec![WatchProvider {
                 logo_path: "/".to_string(),
+                logo_url: None,
                 provider_id: 8,
                 provider_name: "Netflix".to_string(),
             }]),
             runtime: Some(Runtime::from_string("Average")),
             decade: Some(Decade::from_string("Recent")),
             feedback: None,
+            region: None,
+            monetization_types: None,
+            with_cast: None,
+            with_crew: None,
+            media_type: None,
+            language: None,
+            quality: None,
+            vote_bounds: None,
+            sort: None,
+            seen: None,
+            diversity: None,
         }
     }
 
@@ -231,6 +702,52 @@ mod tests {
         .expect("Error parsing file")
     }
 
+    /* Bare movie called "Movie {id}" - discover returns in memory movies in the order they were added */
+    fn numbered_movie(id: i64) -> Movie {
+        Movie {
+            id,
+            overview: String::new(),
+            popularity: 0.0,
+            poster_path: None,
+            poster_url: None,
+            release_date: "2015-01-01".to_string(),
+            title: format!("Movie {}", id),
+            vote_average: 0.0,
+            vote_count: 0,
+            genre_ids: vec![],
+        }
+    }
+
+    /* Bare details for numbered_movie(id) - no runtime, votes or collection */
+    fn numbered_details(id: i64) -> MovieDetails {
+        MovieDetails {
+            id,
+            title: format!("Movie {}", id),
+            overview: String::new(),
+            tagline: None,
+            runtime: None,
+            genres: vec![],
+            vote_average: 0.0,
+            vote_count: 0,
+            budget: 0,
+            imdb_id: None,
+            poster_path: None,
+            poster_url: None,
+            backdrop_path: None,
+            backdrop_url: None,
+            release_date: String::new(),
+            spoken_languages: vec![],
+            belongs_to_collection: None,
+        }
+    }
+
+    /* Catalog of movies 1 to count, in that order */
+    fn numbered_movies(count: i64) -> InMemoryCatalog {
+        (1..=count).fold(InMemoryCatalog::new(), |catalog, id| {
+            catalog.with_movie(numbered_movie(id))
+        })
+    }
+
     async fn build_mock_endpoint(movie_id: &i64, api_key: &String) -> Mock<'static> {
         let keywords_response = get_json_from_file(&movie_id.to_string());
 
@@ -245,7 +762,8 @@ mod tests {
     #[tokio::test]
     async fn test_keyword_process() {
         let api_key = String::from("supersecret");
-        let tmdb = Tmdb::mock_shared_instance(api_key.clone(), MOCK_TMDB_VALID.base_url());
+        let tmdb: Arc<dyn MovieCatalog> =
+            Tmdb::mock_shared_instance(api_key.clone(), MOCK_TMDB_VALID.base_url());
 
         let thumbs_up_ids = vec![123, 456, 789];
         let thumbs_down_ids = vec![321, 654, 987];
@@ -253,15 +771,21 @@ mod tests {
         let mut downvote_map: HashMap<i64, Mock<'static>> = HashMap::new();
 
         for id in &thumbs_up_ids {
-            upvote_map.insert(id.clone(), build_mock_endpoint(id, &api_key).await);
+            upvote_map.insert(*id, build_mock_endpoint(id, &api_key).await);
         }
 
         for id in &thumbs_down_ids {
-            downvote_map.insert(id.clone(), build_mock_endpoint(id, &api_key).await);
+            downvote_map.insert(*id, build_mock_endpoint(id, &api_key).await);
         }
 
-        let (mut criteria_upvotes, mut criteria_downvotes) =
-            process_feedback(tmdb, thumbs_up_ids, thumbs_down_ids).await;
+        let weights =
+            process_feedback(tmdb, MediaType::Movie, thumbs_up_ids, thumbs_down_ids).await;
+
+        let mut profile = KeywordProfile::default();
+        profile.record(&weights, 1.0);
+
+        let mut criteria_upvotes = profile.liked(PROFILE_DISCOVER_KEYWORDS);
+        let mut criteria_downvotes = profile.disliked(PROFILE_DISCOVER_KEYWORDS);
 
         criteria_upvotes.sort();
         criteria_downvotes.sort();
@@ -277,47 +801,343 @@ mod tests {
         assert!(!criteria_downvotes.is_empty());
 
         let expected_upvotes = vec![3210, 5678, 6523];
-        let expected_downvotes = vec![1111, 8888, 9999, 91011];
+        // 9999 and 91011 were voted up and down equally, so they're neither
+        let expected_downvotes = vec![1111, 8888];
 
         assert_eq!(criteria_upvotes, expected_upvotes);
         assert_eq!(criteria_downvotes, expected_downvotes);
     }
 
     #[tokio::test]
-    async fn test_keyword_refinement() {
-        let mut upvotes: HashMap<i64, i16> = HashMap::new();
-        let mut downvotes: HashMap<i64, i16> = HashMap::new();
-
-        upvotes.insert(4444, 4);
-        upvotes.insert(3333, 3);
-        upvotes.insert(2222, 2);
-        upvotes.insert(1111, 1);
-
-        downvotes.insert(4444, 4);
-        downvotes.insert(2222, 1);
-        downvotes.insert(1111, 3);
-        downvotes.insert(3333, 2);
-
-        let (refined_upvotes, refined_downvotes) = refine_keywords(upvotes, downvotes).await;
-
-        assert!(!refined_upvotes.is_empty());
-        assert!(!refined_downvotes.is_empty());
-
-        // Upvotes should have 2222 and 3333
-        assert!(!refined_upvotes.contains_key(&4444));
-        assert!(refined_upvotes.contains_key(&3333));
-        assert_eq!(refined_upvotes.get(&3333).unwrap(), &3i16);
-        assert!(refined_upvotes.contains_key(&2222));
-        assert_eq!(refined_upvotes.get(&2222).unwrap(), &2i16);
-        assert!(!refined_upvotes.contains_key(&1111));
-
-        // Downvotes should have 4444 and 1111
-        assert!(refined_downvotes.contains_key(&4444));
-        assert_eq!(refined_downvotes.get(&4444).unwrap(), &4i16);
-        assert!(!refined_downvotes.contains_key(&2222));
-        assert!(refined_downvotes.contains_key(&1111));
-        assert_eq!(refined_downvotes.get(&1111).unwrap(), &3i16);
-        assert!(!refined_downvotes.contains_key(&3333));
+    async fn test_keyword_process_in_memory() {
+        let keyword = |id: i64, name: &str| Keyword {
+            id,
+            name: name.to_string(),
+        };
+
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(
+            InMemoryCatalog::new()
+                .with_keywords(1, vec![keyword(10, "heist"), keyword(20, "sequel")])
+                .with_keywords(2, vec![keyword(10, "heist"), keyword(30, "time travel")])
+                .with_keywords(3, vec![keyword(20, "sequel"), keyword(40, "musical")]),
+        );
+
+        // Movie 4 has no keywords at all, which shouldn't stop the rest from counting
+        let weights = process_feedback(catalog, MediaType::Movie, vec![1, 2, 4], vec![3]).await;
+
+        assert_eq!(
+            weights,
+            HashMap::from([(10, 2.0), (20, 0.0), (30, 1.0), (40, -1.0)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_sort_in_memory() {
+        let catalog = numbered_movies(40);
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(catalog);
+
+        let popular = RecommendationCriteria::default();
+        let random = RecommendationCriteria {
+            sort: Some(SortBy::Random),
+            ..RecommendationCriteria::default()
+        };
+
+        let ids = |recs: Vec<AsyncRecommendation>| -> Vec<i64> {
+            recs.iter().map(|r| r.movie.id).collect()
+        };
+
+        let popular_ids = ids(get_movie_recommendations(Arc::clone(&catalog), &popular)
+            .await
+            .unwrap());
+        assert_eq!(popular_ids, (1..=11).collect::<Vec<i64>>());
+
+        let mut random_ids = ids(get_movie_recommendations(catalog, &random).await.unwrap());
+        random_ids.sort();
+        random_ids.dedup();
+
+        // Still a full batch of unique movies, drawn from the whole pool
+        assert_eq!(random_ids.len(), NUM_RECOMMENDATIONS);
+        assert!(random_ids.iter().all(|id| (1..=40).contains(id)));
+    }
+
+    #[tokio::test]
+    async fn test_seen_movies_in_memory() {
+        let catalog = numbered_movies(40);
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(catalog);
+
+        let mut criteria = RecommendationCriteria::default();
+        // Shown in earlier batches, then voted on
+        criteria.mark_seen(MediaType::Movie, 1..=11);
+        criteria.mark_seen(MediaType::Movie, [15, 30]);
+        // Shows are numbered separately, so these don't hide any movies
+        criteria.mark_seen(MediaType::Tv, 12..=14);
+
+        let ids: Vec<i64> = get_movie_recommendations(catalog, &criteria)
+            .await
+            .unwrap()
+            .iter()
+            .map(|rec| rec.movie.id)
+            .collect();
+
+        // The first discover page is mostly seen, so the second fills out the batch
+        assert_eq!(ids, vec![12, 13, 14, 16, 17, 18, 19, 20, 21, 22, 23]);
+    }
+
+    #[tokio::test]
+    async fn test_relevance_ranking_in_memory() {
+        let heist = Keyword {
+            id: 10051,
+            name: "heist".to_string(),
+        };
+        let catalog = (1..=30).fold(InMemoryCatalog::new(), |catalog, id| {
+            catalog.with_movie(Movie {
+                genre_ids: match id % 2 {
+                    0 => vec![80],
+                    _ => vec![],
+                },
+                ..numbered_movie(id)
+            })
+        });
+        // Well outside the top 11 by popularity, but it's the only heist movie
+        let catalog: Arc<dyn MovieCatalog> =
+            Arc::new(catalog.with_keywords(25, vec![heist.clone()]));
+
+        let criteria = RecommendationCriteria {
+            genres: Some(vec![Genre {
+                id: 80,
+                name: "Crime".to_string(),
+            }]),
+            feedback: Some(Feedback {
+                like: Some(vec![heist.id]),
+                keyword_profile: Some(KeywordProfile {
+                    weights: HashMap::from([(heist.id, 2.0)]),
+                    rounds: 1,
+                }),
+                ..Feedback::default()
+            }),
+            ..RecommendationCriteria::default()
+        };
+
+        let recs = get_movie_recommendations(catalog, &criteria).await.unwrap();
+        let ids: Vec<i64> = recs.iter().map(|r| r.movie.id).collect();
+
+        // Then crime movies, most popular first
+        assert_eq!(ids[0], 25);
+        assert_eq!(&ids[1..4], &[2, 4, 6]);
+        assert_eq!(ids.len(), NUM_RECOMMENDATIONS);
+    }
+
+    #[tokio::test]
+    async fn test_diversity_in_memory() {
+        let catalog = numbered_movies(20);
+        // The three most popular movies are one franchise
+        let catalog = (1..=3).fold(catalog, |catalog, id| {
+            catalog.with_details(MovieDetails {
+                belongs_to_collection: Some(Collection {
+                    id: 10,
+                    name: "Trilogy".to_string(),
+                    poster_path: None,
+                    backdrop_path: None,
+                }),
+                ..numbered_details(id)
+            })
+        });
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(catalog);
+
+        let ids = |recs: Vec<AsyncRecommendation>| -> Vec<i64> {
+            recs.iter().map(|r| r.movie.id).collect()
+        };
+
+        let diverse = RecommendationCriteria {
+            diversity: Some(0.3),
+            ..RecommendationCriteria::default()
+        };
+        let diverse = ids(get_movie_recommendations(Arc::clone(&catalog), &diverse)
+            .await
+            .unwrap());
+
+        // The sequels drop back behind movies that aren't like anything picked yet
+        assert_eq!(&diverse[..4], &[1, 4, 5, 6]);
+        assert!(diverse.contains(&2));
+        assert_eq!(diverse.len(), NUM_RECOMMENDATIONS);
+
+        // Sessions that haven't asked for diversity get plain popularity order
+        let plain = ids(
+            get_movie_recommendations(catalog, &RecommendationCriteria::default())
+                .await
+                .unwrap(),
+        );
+
+        assert_eq!(plain, (1..=11).collect::<Vec<i64>>());
+    }
+
+    #[tokio::test]
+    async fn test_more_like_this_in_memory() {
+        let movie = |id: i64, release_date: &str| Movie {
+            release_date: release_date.to_string(),
+            ..numbered_movie(id)
+        };
+        let details = |id: i64, runtime: i32| MovieDetails {
+            runtime: Some(runtime),
+            vote_average: 7.0,
+            vote_count: 100,
+            ..numbered_details(id)
+        };
+        let provider = |provider_id: i32, provider_name: &str| WatchProvider {
+            logo_path: "/".to_string(),
+            logo_url: None,
+            provider_id,
+            provider_name: provider_name.to_string(),
+        };
+        let streaming_on = |provider: WatchProvider| {
+            let mut regions = WatchProviderRegions::new();
+            regions.insert(
+                "US".to_string(),
+                WatchProviderRegion {
+                    flatrate: vec![provider],
+                    ..WatchProviderRegion::default()
+                },
+            );
+            regions
+        };
+        let netflix = provider(8, "Netflix");
+        let hulu = provider(15, "Hulu");
+
+        // 3 comes back from both lists, 4 is from the wrong decade, 5 is too long and 6 is on the wrong service
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(
+            InMemoryCatalog::new()
+                .with_movie(movie(2, "2015-05-01"))
+                .with_movie(movie(3, "2016-05-01"))
+                .with_movie(movie(4, "1999-05-01"))
+                .with_movie(movie(5, "2017-05-01"))
+                .with_movie(movie(6, "2018-05-01"))
+                .with_movie_recommendations(1, vec![2, 3, 4])
+                .with_similar(1, vec![1, 3, 5, 6])
+                .with_details(details(2, 100))
+                .with_details(details(3, 95))
+                .with_details(details(4, 100))
+                .with_details(details(5, 150))
+                .with_details(details(6, 100))
+                .with_watch_providers(2, streaming_on(netflix.clone()))
+                .with_watch_providers(3, streaming_on(netflix.clone()))
+                .with_watch_providers(4, streaming_on(netflix.clone()))
+                .with_watch_providers(5, streaming_on(netflix.clone()))
+                .with_watch_providers(6, streaming_on(hulu))
+                .with_videos(
+                    2,
+                    vec![Video {
+                        key: "dQw4w9WgXcQ".to_string(),
+                        name: "Official Trailer".to_string(),
+                        site: "YouTube".to_string(),
+                        video_type: "Trailer".to_string(),
+                        official: true,
+                        size: 1080,
+                        iso_639_1: "en".to_string(),
+                        published_at: String::new(),
+                    }],
+                ),
+        );
+
+        let criteria = RecommendationCriteria {
+            watch_providers: Some(vec![netflix.clone()]),
+            runtime: Some(Runtime::Average),
+            decade: Some(Decade::TwentyTens),
+            ..RecommendationCriteria::default()
+        };
+
+        let recs = get_more_like_this(Arc::clone(&catalog), 1, &criteria)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = recs.iter().map(|r| r.movie.id).collect();
+
+        assert_eq!(ids, vec![2, 3]);
+        assert_eq!(recs[0].providers.flatrate, vec![netflix.clone()]);
+        assert_eq!(
+            recs[0].trailer.as_ref().map(|t| t.key.as_str()),
+            Some("dQw4w9WgXcQ")
+        );
+        assert_eq!(recs[1].trailer, None);
+        assert_eq!(recs[0].explanation.providers, vec![netflix]);
+        assert_eq!(recs[0].explanation.decade, Some(Decade::TwentyTens));
+        // The runtime was checked against the movie's details, so it counts as a reason
+        assert_eq!(recs[0].explanation.runtime, Some(Runtime::Average));
+
+        // Nothing picked yet means nothing is filtered out, apart from the movie itself
+        let recs = get_more_like_this(catalog, 1, &RecommendationCriteria::default())
+            .await
+            .unwrap();
+        let ids: Vec<i64> = recs.iter().map(|r| r.movie.id).collect();
+
+        assert_eq!(ids, vec![2, 3, 4, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_tv_recommendations_in_memory() {
+        let show = |id: i64, name: &str| TvShow {
+            id,
+            name: name.to_string(),
+            overview: String::new(),
+            poster_path: None,
+            poster_url: None,
+            first_air_date: "2016-07-15".to_string(),
+            genre_ids: vec![],
+        };
+        let netflix = WatchProvider {
+            logo_path: "/pbpMk2JmcoNnQwx5JGpXngfoWtp.jpg".to_string(),
+            logo_url: None,
+            provider_id: 8,
+            provider_name: "Netflix".to_string(),
+        };
+        let mut regions = WatchProviderRegions::new();
+        regions.insert(
+            "US".to_string(),
+            WatchProviderRegion {
+                flatrate: vec![netflix.clone()],
+                ..WatchProviderRegion::default()
+            },
+        );
+
+        // Movie 1 shares an ID with show 1, but a TV session should never see it
+        let catalog: Arc<dyn MovieCatalog> = Arc::new(
+            InMemoryCatalog::new()
+                .with_movie(Movie {
+                    release_date: "2016-01-01".to_string(),
+                    title: "Not a show".to_string(),
+                    ..numbered_movie(1)
+                })
+                .with_show(show(1, "Stranger Things"))
+                .with_tv_watch_providers(1, regions)
+                .with_tv_keywords(
+                    1,
+                    vec![Keyword {
+                        id: 10,
+                        name: "small town".to_string(),
+                    }],
+                ),
+        );
+
+        let criteria = RecommendationCriteria {
+            media_type: Some(MediaType::Tv),
+            watch_providers: Some(vec![netflix.clone()]),
+            decade: Some(Decade::TwentyTens),
+            ..RecommendationCriteria::default()
+        };
+
+        let recs = get_tv_recommendations(Arc::clone(&catalog), &criteria)
+            .await
+            .unwrap();
+
+        assert_eq!(recs.len(), 1);
+        for rec in recs {
+            assert_eq!(rec.show.name, "Stranger Things");
+            assert_eq!(
+                rec.async_providers.await.unwrap().unwrap().flatrate,
+                vec![netflix.clone()]
+            );
+        }
+
+        let weights = process_feedback(catalog, MediaType::Tv, vec![1], vec![]).await;
+        assert_eq!(weights, HashMap::from([(10, 1.0)]));
     }
 
     #[tokio::test]
@@ -357,12 +1177,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_keyword_list() {
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let movie_ids = vec![82702, 62177];
 
         let future_response: Vec<AsyncFeedback> =
-            get_keyword_futures(&tmdb, movie_ids.clone()).await;
+            get_keyword_futures(&tmdb, MediaType::Movie, movie_ids.clone()).await;
 
         assert!(!future_response.is_empty());
 
@@ -373,11 +1193,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_keyword_futures() {
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let movie_ids = vec![82702, 62177];
 
-        let future_response = get_keyword_futures(&tmdb, movie_ids.clone()).await;
+        let future_response = get_keyword_futures(&tmdb, MediaType::Movie, movie_ids.clone()).await;
 
         assert!(!future_response.is_empty());
 
@@ -389,6 +1209,10 @@ mod tests {
 
             assert!(keywords.is_ok());
 
+            let keywords = keywords.unwrap();
+
+            assert!(keywords.is_ok());
+
             assert!(!keywords.unwrap().keywords.is_empty());
         }
     }
@@ -396,7 +1220,7 @@ mod tests {
     #[tokio::test]
     async fn test_recommendations() {
         let session_id = String::from("123-456-789");
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let criteria = get_criteria();
 
@@ -404,21 +1228,22 @@ mod tests {
             .await
             .expect("Error interacting with redis");
 
-        let recommendations = get_recommendations_for_session(tmdb, session_id.clone()).await;
+        let recommendations =
+            get_recommendations_for_session(tmdb, session_id.clone(), None, None).await;
 
         assert!(recommendations.is_ok());
-        let recommendations = recommendations.unwrap();
-
-        assert!(!recommendations.is_empty());
+        match recommendations.unwrap().1 {
+            SessionRecommendations::Movies(recs) => assert!(!recs.is_empty()),
+            SessionRecommendations::Tv(_) => panic!("Expected movie recommendations"),
+        }
 
         redis_helper::end_session(session_id).await;
     }
 
     #[tokio::test]
-    #[should_panic(expected = "No genres for ID")]
     async fn test_recommendations_no_genre() {
         let session_id = String::from("987-654-321");
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let mut criteria = get_criteria();
 
@@ -428,16 +1253,20 @@ mod tests {
             .await
             .expect("Error interacting with redis");
 
-        let recommendations = get_recommendations_for_session(tmdb, session_id.clone()).await;
-
+        let recommendations =
+            get_recommendations_for_session(tmdb, session_id.clone(), None, None).await;
         redis_helper::end_session(session_id).await;
+
+        assert!(matches!(
+            recommendations,
+            Err(SessionError::Incomplete("genres"))
+        ));
     }
 
     #[tokio::test]
-    #[should_panic(expected = "No watch providers for ID")]
     async fn test_recommendations_no_providers() {
         let session_id = String::from("555-555-555");
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let mut criteria = get_criteria();
 
@@ -447,15 +1276,20 @@ mod tests {
             .await
             .expect("Error interacting with redis");
 
-        let recommendations = get_recommendations_for_session(tmdb, session_id.clone()).await;
+        let recommendations =
+            get_recommendations_for_session(tmdb, session_id.clone(), None, None).await;
         redis_helper::end_session(session_id).await;
+
+        assert!(matches!(
+            recommendations,
+            Err(SessionError::Incomplete("watch providers"))
+        ));
     }
 
     #[tokio::test]
-    #[should_panic(expected = "No runtime for ID")]
     async fn test_recommendations_no_runtime() {
         let session_id = String::from("545-789-123");
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let mut criteria = get_criteria();
 
@@ -465,15 +1299,20 @@ mod tests {
             .await
             .expect("Error interacting with redis");
 
-        let recommendations = get_recommendations_for_session(tmdb, session_id.clone()).await;
+        let recommendations =
+            get_recommendations_for_session(tmdb, session_id.clone(), None, None).await;
         redis_helper::end_session(session_id).await;
+
+        assert!(matches!(
+            recommendations,
+            Err(SessionError::Incomplete("runtime"))
+        ));
     }
 
     #[tokio::test]
-    #[should_panic(expected = "No decade for ID")]
     async fn test_recommendations_no_decade() {
         let session_id = String::from("222-222-222");
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
 
         let mut criteria = get_criteria();
 
@@ -483,20 +1322,33 @@ mod tests {
             .await
             .expect("Error interacting with redis");
 
-        let recommendations = get_recommendations_for_session(tmdb, session_id.clone()).await;
+        let recommendations =
+            get_recommendations_for_session(tmdb, session_id.clone(), None, None).await;
         redis_helper::end_session(session_id).await;
+
+        assert!(matches!(
+            recommendations,
+            Err(SessionError::Incomplete("decade"))
+        ));
     }
 
     #[tokio::test]
     async fn test_providers() {
-        let tmdb = Tmdb::shared_instance();
+        let tmdb: Arc<dyn MovieCatalog> = Tmdb::shared_instance();
         let movie_id: i64 = 438631;
 
-        let providers = get_providers_from_id(&tmdb, movie_id).await;
+        let providers = get_providers_from_id(
+            tmdb.as_ref(),
+            movie_id,
+            DEFAULT_REGION,
+            &[MonetizationType::Flatrate],
+        )
+        .await;
 
         assert!(providers.is_ok());
         let providers = providers.unwrap();
 
-        assert!(!providers.is_empty());
+        assert!(!providers.flatrate.is_empty());
+        assert!(providers.rent.is_empty());
     }
 }